@@ -38,7 +38,7 @@ async fn send_message(client: &LLMClient, messages: Vec<LLMMessage>) -> Result<L
     let mut payload = json!({
         "model": client.default_model(),
         "messages": anthropic_messages,
-        "max_tokens": ANTHROPIC_MAX_TOKENS
+        "max_tokens": client.max_tokens().unwrap_or(ANTHROPIC_MAX_TOKENS)
     });
 
     if let Some(system) = system_prompt {
@@ -141,6 +141,10 @@ fn convert_message_content_to_anthropic(content: Vec<LLMMessageType>) -> Vec<Val
                     }
                 }));
             }
+            LLMMessageType::FILE { file_uri, .. } => parts.push(json!({
+                "type": "text",
+                "text": format!("[file: {file_uri}]")
+            })),
         }
     }
 
@@ -30,6 +30,15 @@ pub fn convert_body_parts_gemini(body_part: Vec<LLMMessageType>) -> Vec<Value> {
                     }
                 })
             }
+            LLMMessageType::FILE {
+                file_uri,
+                mime_type,
+            } => json!({
+                "fileData": {
+                    "mimeType": mime_type,
+                    "fileUri": file_uri
+                }
+            }),
         })
         .collect()
 }
@@ -55,6 +64,16 @@ fn role_to_str(role: LLMUserType) -> &'static str {
 pub async fn send_generate_request(
     api_client: &LLMClient,
     body_part: Vec<LLMMessage>,
+) -> Result<GeminiResponse> {
+    send_generate_request_with_config(api_client, body_part, None).await
+}
+
+/// Same as `send_generate_request`, with an optional `generationConfig`
+/// object (seed, temperature, response modalities, ...) added to the body.
+pub async fn send_generate_request_with_config(
+    api_client: &LLMClient,
+    body_part: Vec<LLMMessage>,
+    generation_config: Option<Value>,
 ) -> Result<GeminiResponse> {
     let endpoint = api_client.endpoint().trim_end_matches('/');
     let url = format!(
@@ -63,9 +82,12 @@ pub async fn send_generate_request(
         api_client.default_model()
     );
 
-    let body = json!({
+    let mut body = json!({
         "contents": convert_messages_to_gemini_contents(body_part)
     });
+    if let Some(config) = generation_config {
+        body["generationConfig"] = config;
+    }
 
     //log_request_payload(&url, &body);
 
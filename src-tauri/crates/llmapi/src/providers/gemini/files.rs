@@ -0,0 +1,213 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::DateTime;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::types::{LLMClient, LLMMessageType};
+use crate::utils::{content_hash, current_timestamp_millis};
+
+use super::models::{GeminiFile, GeminiFileResponse};
+
+/// Uploaded files are kept by Gemini for 48 hours; this is only assumed when
+/// the upload response has no `expirationTime`.
+const FILE_TTL_SECS: u64 = 48 * 60 * 60;
+/// Cached files are treated as stale this long before Gemini deletes them, so
+/// a request never references a file that just expired.
+const EXPIRY_MARGIN_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedFile {
+    pub name: String,
+    pub uri: String,
+    pub mime_type: String,
+    pub expires_at: u64,
+}
+
+impl CachedFile {
+    pub fn to_message_part(&self) -> LLMMessageType {
+        LLMMessageType::file(self.uri.clone(), self.mime_type.clone())
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+/// Maps uploaded content to its Files API URI so identical references are
+/// only uploaded once while the remote copy is still alive. Files belong to
+/// the project of the key that uploaded them, so entries are keyed by the API
+/// key as well as the content.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FileUriCache {
+    #[serde(default)]
+    entries: HashMap<String, CachedFile>,
+}
+
+impl FileUriCache {
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+            return Ok(Self::default());
+        }
+
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read file cache {:?}", path))?;
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to decode file cache {:?}", path))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self).context("Failed to encode file cache")?;
+        tokio::fs::write(path, contents)
+            .await
+            .with_context(|| format!("Failed to write file cache {:?}", path))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&CachedFile> {
+        let now = current_timestamp_secs();
+        self.entries.get(key).filter(|entry| !entry.is_expired(now))
+    }
+
+    pub fn insert(&mut self, key: String, file: CachedFile) {
+        self.entries.insert(key, file);
+    }
+
+    /// Adds entries from `other`, keeping whichever copy of a file lives longer.
+    pub fn merge(&mut self, other: FileUriCache) {
+        for (key, file) in other.entries {
+            match self.entries.get(&key) {
+                Some(existing) if existing.expires_at >= file.expires_at => {}
+                _ => {
+                    self.entries.insert(key, file);
+                }
+            }
+        }
+    }
+
+    pub fn prune_expired(&mut self) {
+        let now = current_timestamp_secs();
+        self.entries.retain(|_, entry| !entry.is_expired(now));
+    }
+}
+
+/// Uploads `bytes` through the resumable Files API protocol.
+pub async fn upload_file(
+    api_client: &LLMClient,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    display_name: &str,
+) -> Result<GeminiFile> {
+    let client = Client::new();
+
+    let start_response = client
+        .post(upload_url(api_client.endpoint()))
+        .header("x-goog-api-key", api_client.api_key())
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header(
+            "X-Goog-Upload-Header-Content-Length",
+            bytes.len().to_string(),
+        )
+        .header("X-Goog-Upload-Header-Content-Type", mime_type)
+        .header("Content-Type", "application/json")
+        .json(&json!({ "file": { "display_name": display_name } }))
+        .send()
+        .await
+        .context("HTTP request (files upload start) failed")?
+        .error_for_status()
+        .context("Gemini rejected the file upload")?;
+
+    let session_url = start_response
+        .headers()
+        .get("x-goog-upload-url")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Gemini did not return an upload URL"))?;
+
+    let response_text = client
+        .post(session_url)
+        .header("Content-Length", bytes.len().to_string())
+        .header("X-Goog-Upload-Offset", "0")
+        .header("X-Goog-Upload-Command", "upload, finalize")
+        .body(bytes)
+        .send()
+        .await
+        .context("HTTP request (files upload) failed")?
+        .error_for_status()
+        .context("Gemini failed to store the uploaded file")?
+        .text()
+        .await
+        .context("Reading files upload response body failed")?;
+
+    let parsed: GeminiFileResponse = serde_json::from_str(&response_text)
+        .with_context(|| format!("Failed to decode file upload JSON. Raw: {}", response_text))?;
+
+    Ok(parsed.file)
+}
+
+/// Returns the cached upload for `bytes`, uploading it first when the cache
+/// has no live entry for its content hash.
+pub async fn upload_file_cached(
+    api_client: &LLMClient,
+    cache: &mut FileUriCache,
+    bytes: Vec<u8>,
+    mime_type: &str,
+    display_name: &str,
+) -> Result<CachedFile> {
+    let key = cache_key(api_client.api_key(), &bytes);
+    if let Some(cached) = cache.get(&key) {
+        return Ok(cached.clone());
+    }
+
+    let file = upload_file(api_client, bytes, mime_type, display_name).await?;
+    let expires_at = file
+        .expiration_time
+        .as_deref()
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .and_then(|time| u64::try_from(time.timestamp()).ok())
+        .unwrap_or_else(|| current_timestamp_secs() + FILE_TTL_SECS)
+        .saturating_sub(EXPIRY_MARGIN_SECS);
+    let cached = CachedFile {
+        name: file.name,
+        uri: file.uri,
+        mime_type: file.mime_type.unwrap_or_else(|| mime_type.to_string()),
+        expires_at,
+    };
+
+    cache.insert(key, cached.clone());
+    Ok(cached)
+}
+
+/// Hash of the API key and the content hash, so a file uploaded with one key
+/// is never offered to another and the key itself is not written to disk.
+fn cache_key(api_key: &str, bytes: &[u8]) -> String {
+    content_hash(format!("{}:{}", api_key, content_hash(bytes)).as_bytes())
+}
+
+/// Derives `https://host/upload/v1beta/files` from a configured endpoint such
+/// as `https://host/v1beta` or `https://host/v1beta/models`.
+fn upload_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = endpoint.strip_suffix("/models").unwrap_or(endpoint);
+
+    let path_start = endpoint
+        .find("://")
+        .map(|scheme_end| scheme_end + 3)
+        .and_then(|host_start| endpoint[host_start..].find('/').map(|idx| host_start + idx))
+        .unwrap_or(endpoint.len());
+
+    let (origin, version_path) = endpoint.split_at(path_start);
+    format!("{origin}/upload{version_path}/files")
+}
+
+fn current_timestamp_secs() -> u64 {
+    current_timestamp_millis() / 1000
+}
@@ -1,9 +1,11 @@
 mod api;
+mod files;
 pub mod models;
 pub use api::{
     convert_body_parts_gemini, gemini_embed_texts, response_to_base64_images,
-    response_to_text_data, send_generate_request,
+    response_to_text_data, send_generate_request, send_generate_request_with_config,
 };
+pub use files::{CachedFile, FileUriCache, upload_file, upload_file_cached};
 
 use crate::types::{ChatFn, LLMClient, LLMMessage, LLMMessageType};
 use std::sync::Arc;
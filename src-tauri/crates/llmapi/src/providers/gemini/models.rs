@@ -86,3 +86,24 @@ pub struct GeminiBatchEmbedResponse {
 pub struct Embedding {
     pub values: Vec<f32>,
 }
+
+//-----Files API Models-----
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFile {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<String>,
+    pub create_time: Option<String>,
+    pub expiration_time: Option<String>,
+    pub sha256_hash: Option<String>,
+    pub uri: String,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeminiFileResponse {
+    pub file: GeminiFile,
+}
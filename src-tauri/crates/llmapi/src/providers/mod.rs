@@ -1,6 +1,6 @@
 mod anthropic;
 pub mod gemini;
-mod openai;
+pub mod openai;
 
 use crate::types::{ChatFn, LLMClient, LLMProvider};
 
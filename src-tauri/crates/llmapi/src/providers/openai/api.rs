@@ -1,12 +1,16 @@
 use anyhow::{Context, Result, anyhow};
 use reqwest::Client;
+use reqwest::multipart::{Form, Part};
 use serde_json::{Value, json};
 use std::sync::Arc;
 
 use crate::types::{ChatFn, LLMClient, LLMMessage, LLMMessageType, LLMUserType};
 use crate::utils::detect_mime_type;
 
-use super::models::{ChatCompletionResponse, ChatContent, ChatContentPart, EmbeddingResponse};
+use super::models::{
+    ChatCompletionResponse, ChatContent, ChatContentPart, EmbeddingResponse, ImageEditRequest,
+    ImageResponse,
+};
 
 const OPENAI_MAX_TOKENS: u32 = 1024;
 
@@ -37,7 +41,7 @@ async fn send_chat_completion(client: &LLMClient, messages: Vec<LLMMessage>) ->
     let payload = json!({
         "model": client.default_model(),
         "messages": convert_messages_to_openai(messages),
-        "max_tokens": OPENAI_MAX_TOKENS
+        "max_tokens": client.max_tokens().unwrap_or(OPENAI_MAX_TOKENS)
     });
 
     let http_client = Client::new();
@@ -96,10 +100,19 @@ fn convert_message(message: LLMMessage) -> Value {
                     .unwrap_or_else(|| "image/png".to_string());
                 let data_url = format!("data:{mime};base64,{data_b64}");
                 content_items.push(json!({
-                    "type": "input_image",
+                    "type": "image_url",
                     "image_url": { "url": data_url }
                 }));
             }
+            LLMMessageType::FILE { file_uri, .. } => {
+                // Remote file references are provider specific; pass the URI along as text.
+                let text = format!("[file: {file_uri}]");
+                text_segments.push(text.clone());
+                content_items.push(json!({
+                    "type": "text",
+                    "text": text
+                }));
+            }
         }
     }
 
@@ -229,3 +242,119 @@ pub async fn openai_embed_texts(client: &LLMClient, inputs: &[String]) -> Result
 
     Ok(parsed.data.into_iter().map(|item| item.embedding).collect())
 }
+
+/// Sends an image edit (inpainting) request to `/images/edits`.
+pub async fn send_image_edit(
+    client: &LLMClient,
+    request: ImageEditRequest,
+) -> Result<ImageResponse> {
+    let url = format!("{}/images/edits", client.endpoint().trim_end_matches('/'));
+    let extension = request.image_mime.rsplit('/').next().unwrap_or("png");
+
+    let image_part = Part::bytes(request.image)
+        .file_name(format!("image.{extension}"))
+        .mime_str(&request.image_mime)
+        .context("Invalid image MIME type")?;
+
+    let mut form = Form::new()
+        .text("model", client.default_model().to_string())
+        .text("prompt", request.prompt)
+        .part("image", image_part);
+
+    if let Some(mask) = request.mask {
+        let mask_part = Part::bytes(mask)
+            .file_name("mask.png")
+            .mime_str("image/png")
+            .context("Invalid mask MIME type")?;
+        form = form.part("mask", mask_part);
+    }
+
+    if let Some(size) = request.size {
+        form = form.text("size", size);
+    }
+
+    // GPT image models always return base64 and reject `response_format`.
+    if !client.default_model().starts_with("gpt-image") {
+        form = form.text("response_format", "b64_json");
+    }
+
+    let http_client = Client::new();
+    let response = http_client
+        .post(url)
+        .bearer_auth(client.api_key())
+        .multipart(form)
+        .send()
+        .await
+        .context("OpenAI image edit request failed")?;
+
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .context("Failed to read OpenAI image edit response body")?;
+
+    if !status.is_success() {
+        return Err(anyhow!(
+            "OpenAI image edit failed: status {} body {}",
+            status,
+            response_text
+        ));
+    }
+
+    serde_json::from_str(&response_text)
+        .with_context(|| format!("Failed to decode OpenAI image edit JSON: {response_text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_parts_use_the_chat_completions_shape() {
+        let message = LLMMessage::new(
+            None,
+            "Human",
+            vec![
+                LLMMessageType::text("Describe this"),
+                LLMMessageType::IMAGE {
+                    data_b64: "aGk=".to_string(),
+                    file_path: Some("photo.png".to_string()),
+                },
+            ],
+        );
+
+        assert_eq!(
+            convert_message(message),
+            json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Describe this" },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": "data:image/png;base64,aGk=" }
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn file_parts_are_sent_as_text() {
+        let message = LLMMessage::new(
+            None,
+            "Human",
+            vec![LLMMessageType::FILE {
+                file_uri: "https://example.com/files/abc".to_string(),
+                mime_type: "image/png".to_string(),
+            }],
+        );
+
+        assert_eq!(
+            convert_message(message),
+            json!({
+                "role": "user",
+                "content": "[file: https://example.com/files/abc]"
+            })
+        );
+    }
+}
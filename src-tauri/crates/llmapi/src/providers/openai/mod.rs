@@ -1,7 +1,7 @@
 mod api;
-mod models;
+pub mod models;
 
-pub use api::chat;
+pub use api::{chat, send_image_edit};
 
 use crate::types::LLMClient;
 
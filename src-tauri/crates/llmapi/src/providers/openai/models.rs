@@ -50,3 +50,27 @@ pub struct EmbeddingResponse {
 pub struct EmbeddingData {
     pub embedding: Vec<f32>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ImageResponse {
+    #[serde(default)]
+    pub data: Vec<ImageResponseData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageResponseData {
+    pub b64_json: Option<String>,
+    pub url: Option<String>,
+    pub revised_prompt: Option<String>,
+}
+
+/// Inputs for `/images/edits`. The mask must be a PNG the same size as
+/// `image`, with fully transparent pixels marking the area to repaint.
+#[derive(Debug, Clone)]
+pub struct ImageEditRequest {
+    pub image: Vec<u8>,
+    pub image_mime: String,
+    pub mask: Option<Vec<u8>>,
+    pub prompt: String,
+    pub size: Option<String>,
+}
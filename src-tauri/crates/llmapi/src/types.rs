@@ -23,6 +23,10 @@ pub enum LLMMessageType {
         data_b64: String,
         file_path: Option<String>,
     },
+    FILE {
+        file_uri: String,
+        mime_type: String,
+    },
 }
 impl LLMMessageType {
     pub fn text(text: impl Into<String>) -> Self {
@@ -34,6 +38,12 @@ impl LLMMessageType {
             file_path: None,
         }
     }
+    pub fn file(file_uri: impl Into<String>, mime_type: impl Into<String>) -> Self {
+        LLMMessageType::FILE {
+            file_uri: file_uri.into(),
+            mime_type: mime_type.into(),
+        }
+    }
     pub fn image(path_str: impl Into<String>) -> Self {
         let path_str = path_str.into();
         let data_b64 = crate::utils::encode_image_to_base64(&path_str).unwrap_or_default();
@@ -88,6 +98,7 @@ pub struct LLMClient {
     pub(crate) endpoint: String,
     pub(crate) default_model: String,
     pub(crate) llm_type: LLMType,
+    pub(crate) max_tokens: Option<u32>,
 }
 
 impl LLMClient {
@@ -104,9 +115,17 @@ impl LLMClient {
             endpoint: endpoint.into(),
             default_model: default_model.into(),
             llm_type,
+            max_tokens: None,
         }
     }
 
+    /// Caps the length of chat replies for providers that require a limit
+    /// (OpenAI, Anthropic); without it they use a 1024-token default.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
     pub fn provider(&self) -> LLMProvider {
         self.provider
     }
@@ -126,6 +145,10 @@ impl LLMClient {
     pub fn llm_type(&self) -> LLMType {
         self.llm_type
     }
+
+    pub fn max_tokens(&self) -> Option<u32> {
+        self.max_tokens
+    }
 }
 
 
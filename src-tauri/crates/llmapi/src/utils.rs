@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use base64::Engine as _;
 use reqwest::Client;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -49,6 +50,12 @@ pub fn encode_byte_to_base64(bytes: Vec<u8>) -> String {
     let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
     encoded
 }
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
 pub fn current_timestamp_millis() -> u64 {
     let now = std::time::SystemTime::now();
     let duration_since_epoch = now
@@ -0,0 +1,239 @@
+//! Minimal raster drawing for reference annotations: outlined boxes, arrows
+//! and numbered labels rendered with a built-in bitmap digit font.
+
+use image::{Pixel, Rgba, RgbaImage};
+
+use crate::models::{Annotation, PixelPoint};
+use crate::post_process::parse_color;
+
+const DEFAULT_ANNOTATION_COLOR: Rgba<u8> = Rgba([255, 59, 48, 255]);
+const LABEL_TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// 3x5 glyphs for 0-9, one row per byte, most significant of the low three
+/// bits on the left.
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Draws `annotations` into `image`, multiplying their coordinates and stroke
+/// widths by `scale` so they line up after a downscale. Strokes are capped at
+/// a quarter of the shorter side.
+pub fn draw_annotations(
+    image: &mut RgbaImage,
+    annotations: &[Annotation],
+    scale: f32,
+) -> Result<(), String> {
+    let shorter_side = image.width().min(image.height()) as f32;
+    let default_thickness = (shorter_side / 200.0).max(2.0);
+    let max_thickness = (shorter_side / 4.0).max(1.0);
+    let point = |x: i32, y: i32| (x as f32 * scale, y as f32 * scale);
+    let stroke = |thickness: Option<u32>| {
+        thickness
+            .map(|value| (value as f32 * scale).clamp(1.0, max_thickness))
+            .unwrap_or(default_thickness)
+    };
+
+    for annotation in annotations {
+        match annotation {
+            Annotation::Box {
+                x,
+                y,
+                width,
+                height,
+                color,
+                thickness,
+                label,
+            } => {
+                let color = annotation_color(color.as_deref())?;
+                let thickness = stroke(*thickness);
+                let (left, top) = point(*x, *y);
+                let (right, bottom) = (left + *width as f32 * scale, top + *height as f32 * scale);
+                for (from, to) in [
+                    ((left, top), (right, top)),
+                    ((right, top), (right, bottom)),
+                    ((right, bottom), (left, bottom)),
+                    ((left, bottom), (left, top)),
+                ] {
+                    draw_line(image, from, to, thickness, color);
+                }
+                if let Some(number) = label {
+                    draw_label(image, (left, top), *number, thickness, color);
+                }
+            }
+            Annotation::Arrow {
+                from,
+                to,
+                color,
+                thickness,
+            } => {
+                let color = annotation_color(color.as_deref())?;
+                let PixelPoint { x, y } = *from;
+                let start = point(x, y);
+                let PixelPoint { x, y } = *to;
+                let end = point(x, y);
+                draw_arrow(image, start, end, stroke(*thickness), color);
+            }
+            Annotation::Label {
+                x,
+                y,
+                number,
+                color,
+            } => {
+                let color = annotation_color(color.as_deref())?;
+                draw_label(image, point(*x, *y), *number, default_thickness, color);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn annotation_color(value: Option<&str>) -> Result<Rgba<u8>, String> {
+    match value.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => parse_color(value),
+        None => Ok(DEFAULT_ANNOTATION_COLOR),
+    }
+}
+
+/// Blends every pixel within `thickness / 2` of the segment exactly once, so
+/// translucent colours don't build up where the stroke overlaps itself. Only
+/// the part of the segment's bounding box inside the image is visited, however
+/// far off-image the endpoints are.
+fn draw_line(
+    image: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let radius = thickness / 2.0;
+    let left = (from.0.min(to.0) - radius).floor().max(0.0);
+    let top = (from.1.min(to.1) - radius).floor().max(0.0);
+    let right = (from.0.max(to.0) + radius)
+        .ceil()
+        .min(image.width() as f32 - 1.0);
+    let bottom = (from.1.max(to.1) + radius)
+        .ceil()
+        .min(image.height() as f32 - 1.0);
+    if left > right || top > bottom {
+        return;
+    }
+
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length_squared = dx * dx + dy * dy;
+    let limit = (radius * radius).max(0.25);
+    for y in top as u32..=bottom as u32 {
+        for x in left as u32..=right as u32 {
+            let (px, py) = (x as f32 - from.0, y as f32 - from.1);
+            let t = if length_squared > 0.0 {
+                ((px * dx + py * dy) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (ex, ey) = (px - dx * t, py - dy * t);
+            if ex * ex + ey * ey <= limit {
+                image.get_pixel_mut(x, y).blend(&color);
+            }
+        }
+    }
+}
+
+fn draw_arrow(
+    image: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    draw_line(image, from, to, thickness, color);
+
+    let angle = (from.1 - to.1).atan2(from.0 - to.0);
+    let head = (thickness * 4.0).max(12.0);
+    for spread in [-0.5_f32, 0.5] {
+        let tip = (
+            to.0 + head * (angle + spread).cos(),
+            to.1 + head * (angle + spread).sin(),
+        );
+        draw_line(image, to, tip, thickness, color);
+    }
+}
+
+/// Draws `number` in a filled circle centred on `center`.
+fn draw_label(
+    image: &mut RgbaImage,
+    center: (f32, f32),
+    number: u32,
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let digits: Vec<usize> = number
+        .to_string()
+        .bytes()
+        .map(|digit| usize::from(digit - b'0'))
+        .collect();
+    let cell = thickness.round().max(2.0) as i64;
+    let text_width = digits.len() as i64 * 4 * cell - cell;
+    let text_height = 5 * cell;
+    let radius = (text_width.max(text_height) as f32 / 2.0) + 2.0 * cell as f32;
+
+    for (x, y) in disc_pixels(image, center.0, center.1, radius) {
+        image.get_pixel_mut(x, y).blend(&color);
+    }
+
+    let left = center.0.round() as i64 - text_width / 2;
+    let top = center.1.round() as i64 - text_height / 2;
+    for (position, digit) in digits.iter().enumerate() {
+        let glyph_left = left + position as i64 * 4 * cell;
+        for (row, bits) in DIGIT_GLYPHS[*digit].iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                fill_rect(
+                    image,
+                    glyph_left + column * cell,
+                    top + row as i64 * cell,
+                    cell,
+                    LABEL_TEXT_COLOR,
+                );
+            }
+        }
+    }
+}
+
+fn disc_pixels(
+    image: &RgbaImage,
+    cx: f32,
+    cy: f32,
+    radius: f32,
+) -> impl Iterator<Item = (u32, u32)> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let reach = radius.ceil() as i64;
+    let (ox, oy) = (cx.round() as i64, cy.round() as i64);
+    let limit = radius * radius;
+
+    ((oy - reach).max(0)..=(oy + reach).min(height - 1)).flat_map(move |y| {
+        ((ox - reach).max(0)..=(ox + reach).min(width - 1)).filter_map(move |x| {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            (dx * dx + dy * dy <= limit.max(0.25)).then_some((x as u32, y as u32))
+        })
+    })
+}
+
+fn fill_rect(image: &mut RgbaImage, left: i64, top: i64, size: i64, color: Rgba<u8>) {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    for y in top.max(0)..(top + size).min(height) {
+        for x in left.max(0)..(left + size).min(width) {
+            image.get_pixel_mut(x as u32, y as u32).blend(&color);
+        }
+    }
+}
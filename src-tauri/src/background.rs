@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+
+/// Builds a foreground mask (255 = keep) by flood-filling from the image
+/// border through every pixel within `tolerance` of the background colour.
+/// Without an explicit `key` the background colour is the median of the
+/// border pixels, which suits studio shots on a plain backdrop.
+pub fn color_key_mask(image: &DynamicImage, key: Option<Rgba<u8>>, tolerance: u8) -> GrayImage {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let key = key.unwrap_or_else(|| border_median(&rgba));
+    let limit = u32::from(tolerance) * u32::from(tolerance) * 3;
+
+    let mut mask = GrayImage::from_pixel(width, height, Luma([255]));
+    let mut queue = VecDeque::new();
+
+    let visit = |x: u32, y: u32, mask: &mut GrayImage, queue: &mut VecDeque<(u32, u32)>| {
+        if mask.get_pixel(x, y)[0] == 0 {
+            return;
+        }
+        let pixel = rgba.get_pixel(x, y);
+        if pixel[3] == 0 || color_distance(pixel, &key) <= limit {
+            mask.put_pixel(x, y, Luma([0]));
+            queue.push_back((x, y));
+        }
+    };
+
+    for x in 0..width {
+        visit(x, 0, &mut mask, &mut queue);
+        visit(x, height - 1, &mut mask, &mut queue);
+    }
+    for y in 0..height {
+        visit(0, y, &mut mask, &mut queue);
+        visit(width - 1, y, &mut mask, &mut queue);
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        if x > 0 {
+            visit(x - 1, y, &mut mask, &mut queue);
+        }
+        if x + 1 < width {
+            visit(x + 1, y, &mut mask, &mut queue);
+        }
+        if y > 0 {
+            visit(x, y - 1, &mut mask, &mut queue);
+        }
+        if y + 1 < height {
+            visit(x, y + 1, &mut mask, &mut queue);
+        }
+    }
+
+    mask
+}
+
+/// Softens the mask edge so cut-outs do not look jagged.
+pub fn feather_mask(mask: &GrayImage, radius: f32) -> GrayImage {
+    if radius <= 0.0 {
+        return mask.clone();
+    }
+    image::imageops::blur(mask, radius)
+}
+
+/// Multiplies the image's alpha by the mask.
+pub fn apply_mask(image: &DynamicImage, mask: &GrayImage) -> RgbaImage {
+    let mut rgba = image.to_rgba8();
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let coverage = u16::from(mask.get_pixel(x, y)[0]);
+        pixel[3] = ((u16::from(pixel[3]) * coverage) / 255) as u8;
+    }
+    rgba
+}
+
+fn border_median(image: &RgbaImage) -> Rgba<u8> {
+    let (width, height) = image.dimensions();
+    let mut channels: [Vec<u8>; 3] = Default::default();
+
+    let border = (0..width)
+        .flat_map(|x| [(x, 0), (x, height - 1)])
+        .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]));
+    for (x, y) in border {
+        let pixel = image.get_pixel(x, y);
+        for (index, values) in channels.iter_mut().enumerate() {
+            values.push(pixel[index]);
+        }
+    }
+
+    let median = |values: &mut Vec<u8>| {
+        values.sort_unstable();
+        values[values.len() / 2]
+    };
+    let [red, green, blue] = &mut channels;
+    Rgba([median(red), median(green), median(blue), 255])
+}
+
+fn color_distance(a: &Rgba<u8>, b: &Rgba<u8>) -> u32 {
+    (0..3)
+        .map(|index| {
+            let delta = i32::from(a[index]) - i32::from(b[index]);
+            (delta * delta) as u32
+        })
+        .sum()
+}
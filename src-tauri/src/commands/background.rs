@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use image::{DynamicImage, ImageFormat};
+use tokio::fs;
+
+use crate::background::{apply_mask, color_key_mask, feather_mask};
+use crate::commands::generate::record_derived_output;
+use crate::fs_utils::{ensure_output_dir, resolve_library_path, write_beside_output};
+use crate::image_metadata::{embed_metadata, extract_metadata};
+use crate::image_utils::{decode_image, encode_image};
+use crate::lineage::GenerationOperation;
+use crate::models::{RemoveBackgroundRequest, RemoveBackgroundResponse};
+use crate::onnx::run_segmentation;
+use crate::post_process::parse_color;
+
+const DEFAULT_SEGMENTATION_SIZE: u32 = 320;
+const MAX_SEGMENTATION_SIZE: u32 = 2048;
+const DEFAULT_KEY_TOLERANCE: u8 = 32;
+
+/// Encoded results of the blocking cut-out step.
+struct ProcessedCutout {
+    cutout: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    warnings: Vec<String>,
+}
+
+/// Cuts the subject out of an output and saves it as an RGBA PNG beside the
+/// source. Uses a user-supplied ONNX segmentation model when `model_path` is
+/// set, otherwise, or when the model can't run, flood-fills the backdrop from
+/// the image border by colour.
+#[tauri::command]
+pub async fn remove_background(
+    payload: RemoveBackgroundRequest,
+) -> Result<RemoveBackgroundResponse, String> {
+    let source_path = resolve_library_path(&ensure_output_dir().await?, &payload.id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", payload.id))?;
+    let source = fs::read(&source_path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", payload.id, err))?;
+    let metadata = extract_metadata(&source).unwrap_or(None);
+
+    let model_path = payload
+        .model_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    let input_size = payload
+        .model_input_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_SEGMENTATION_SIZE);
+    if input_size > MAX_SEGMENTATION_SIZE {
+        return Err(format!(
+            "Model input size must be at most {} px, got {}.",
+            MAX_SEGMENTATION_SIZE, input_size
+        ));
+    }
+    let key_color = payload
+        .key_color
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+        .map(parse_color)
+        .transpose()?;
+    let tolerance = payload.tolerance.unwrap_or(DEFAULT_KEY_TOLERANCE);
+    let feather = payload.feather.unwrap_or(0.0);
+    let save_mask = payload.save_mask;
+
+    // Decoding, segmentation and encoding are CPU bound; keep them off the
+    // async runtime.
+    let ProcessedCutout {
+        cutout,
+        mask,
+        warnings,
+    } = tokio::task::spawn_blocking(move || -> Result<ProcessedCutout, String> {
+        let image = decode_image(&source)?;
+
+        let mut warnings = Vec::new();
+        let mask = match &model_path {
+            Some(path) => run_segmentation(path, &image, input_size).unwrap_or_else(|err| {
+                warnings.push(format!(
+                    "Segmentation failed, used colour keying instead: {}",
+                    err
+                ));
+                color_key_mask(&image, key_color, tolerance)
+            }),
+            None => color_key_mask(&image, key_color, tolerance),
+        };
+        let mask = feather_mask(&mask, feather);
+
+        let cutout = DynamicImage::ImageRgba8(apply_mask(&image, &mask));
+        let cutout = encode_image(&cutout, ImageFormat::Png)?;
+        let mask = if save_mask {
+            Some(encode_image(
+                &DynamicImage::ImageLuma8(mask),
+                ImageFormat::Png,
+            )?)
+        } else {
+            None
+        };
+        Ok(ProcessedCutout {
+            cutout,
+            mask,
+            warnings,
+        })
+    })
+    .await
+    .map_err(|err| format!("Background removal task failed: {}", err))??;
+
+    let cutout = match &metadata {
+        Some(metadata) => embed_metadata(cutout.clone(), metadata).unwrap_or(cutout),
+        None => cutout,
+    };
+
+    let image = write_beside_output(&payload.id, "_nobg", "png", &cutout).await?;
+    let mask = match mask {
+        Some(bytes) => Some(write_beside_output(&payload.id, "_mask", "png", &bytes).await?),
+        None => None,
+    };
+    record_derived_output(
+        &payload.id,
+        &image.id,
+        GenerationOperation::RemoveBackground,
+        metadata.as_ref(),
+    )
+    .await?;
+
+    Ok(RemoveBackgroundResponse {
+        image,
+        mask,
+        warnings,
+    })
+}
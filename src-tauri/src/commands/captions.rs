@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use llmapi::types::{LLMMessageType, LLMProvider};
+use llmapi::utils::content_hash;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::fs;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::fs_utils::{ensure_input_dir, resolve_library_path};
+use crate::llm::{
+    chat_image_part, default_chat_model, extract_json_object, parse_provider, require_api_key,
+    resolve_model, send_chat,
+};
+use crate::models::{CaptionImagesRequest, CaptionImagesResponse, CaptionedImage, ImageCaption};
+use crate::store;
+
+const CAPTION_SUFFIX: &str = ".caption.json";
+/// Images captioned at once; the rest wait for a free slot.
+const CAPTION_CONCURRENCY: usize = 4;
+
+const CAPTION_INSTRUCTION: &str = "Describe the attached image for someone writing an \
+image-generation prompt that uses it as a reference. Reply with JSON only, no other \
+text, in the form {\"short\": \"...\", \"detailed\": \"...\"}. `short` is a plain noun \
+phrase of at most twelve words naming the main subject. `detailed` is two to four \
+sentences covering subject, setting, composition, lighting, colours and style. \
+Describe only what is visible.";
+
+/// The reply shape the model is asked for.
+#[derive(Deserialize)]
+struct CaptionReply {
+    short: String,
+    detailed: String,
+}
+
+/// Captions input images with a vision-capable chat model and stores the
+/// result beside each image. Images whose caption still matches their content
+/// are skipped unless `overwrite` is set; failures are reported per image.
+#[tauri::command]
+pub async fn caption_images(
+    payload: CaptionImagesRequest,
+) -> Result<CaptionImagesResponse, String> {
+    let provider = parse_provider(payload.provider.as_deref())?;
+    let api_key = require_api_key(payload.api_key.as_deref())?.to_string();
+    let model = resolve_model(payload.model.as_deref(), default_chat_model(provider));
+    let dir = ensure_input_dir().await?;
+
+    let permits = Arc::new(Semaphore::new(CAPTION_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for (index, id) in payload.ids.into_iter().enumerate() {
+        let (api_key, model, dir) = (api_key.clone(), model.clone(), dir.clone());
+        let permits = Arc::clone(&permits);
+        let overwrite = payload.overwrite;
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = caption_image(&dir, &id, provider, &api_key, &model, overwrite).await;
+            (index, id, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.map_err(|err| format!("Caption task failed: {}", err))?);
+    }
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let mut captions = Vec::new();
+    let mut failures = Vec::new();
+    for (_, id, result) in results {
+        match result {
+            Ok(caption) => captions.push(CaptionedImage { id, caption }),
+            Err(err) => failures.push(format!("{}: {}", id, err)),
+        }
+    }
+
+    if captions.is_empty() && !failures.is_empty() {
+        return Err(failures.join("\n"));
+    }
+
+    Ok(CaptionImagesResponse { captions, failures })
+}
+
+/// Stored captions for the given input images; images without one are left
+/// out.
+#[tauri::command]
+pub async fn get_image_captions(ids: Vec<String>) -> Result<Vec<CaptionedImage>, String> {
+    let dir = ensure_input_dir().await?;
+    let mut captions = Vec::new();
+    for id in ids {
+        if let Some(caption) = store::load_optional(&caption_path(&dir, &id)?).await? {
+            captions.push(CaptionedImage { id, caption });
+        }
+    }
+    Ok(captions)
+}
+
+/// Captions of the images bound to template slots, as the `captions` render
+/// variable: `{{ captions.img_1.short }}`. Captions written for an earlier
+/// image under the same id are left out, as if the image had none.
+pub(crate) async fn caption_context(
+    images: &HashMap<String, String>,
+) -> Result<Map<String, Value>, String> {
+    let dir = ensure_input_dir().await?;
+    let mut context = Map::new();
+    for (slot, id) in images {
+        let Some(caption) = store::load_optional::<ImageCaption>(&caption_path(&dir, id)?).await?
+        else {
+            continue;
+        };
+        let image_path =
+            resolve_library_path(&dir, id).ok_or_else(|| format!("Invalid image id: {}", id))?;
+        let current = fs::read(&image_path)
+            .await
+            .ok()
+            .map(|bytes| content_hash(&bytes));
+        if current.as_deref() != Some(caption.content_hash.as_str()) {
+            eprintln!(
+                "Ignoring stale caption for '{}'; caption the image again.",
+                id
+            );
+            continue;
+        }
+        let caption = serde_json::to_value(caption)
+            .map_err(|err| format!("Unable to serialise caption for '{}': {}", id, err))?;
+        context.insert(slot.clone(), caption);
+    }
+    Ok(context)
+}
+
+/// Drops the captions of deleted input images.
+pub(crate) async fn remove_captions(ids: &[String]) -> Result<(), String> {
+    let dir = ensure_input_dir().await?;
+    for id in ids {
+        if let Ok(path) = caption_path(&dir, id) {
+            store::remove(&path).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn caption_image(
+    dir: &Path,
+    id: &str,
+    provider: LLMProvider,
+    api_key: &str,
+    model: &str,
+    overwrite: bool,
+) -> Result<ImageCaption, String> {
+    let image_path =
+        resolve_library_path(dir, id).ok_or_else(|| format!("Invalid image id: {}", id))?;
+    let bytes = fs::read(&image_path)
+        .await
+        .map_err(|err| format!("Unable to read image: {}", err))?;
+    let hash = content_hash(&bytes);
+
+    let sidecar = caption_path(dir, id)?;
+    if !overwrite {
+        let existing = store::load_optional::<ImageCaption>(&sidecar)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("Replacing unreadable caption: {err}");
+                None
+            });
+        if let Some(existing) = existing {
+            if existing.content_hash == hash {
+                return Ok(existing);
+            }
+        }
+    }
+
+    let image = chat_image_part(&bytes, "image", provider)?;
+    let text = send_chat(
+        provider,
+        api_key,
+        model,
+        CAPTION_INSTRUCTION.to_string(),
+        vec![image, LLMMessageType::text("Caption this image.")],
+        None,
+    )
+    .await?;
+
+    let reply: CaptionReply = extract_json_object(&text)
+        .ok_or_else(|| format!("The model did not return a caption: {}", text.trim()))
+        .and_then(|json| {
+            serde_json::from_str(json)
+                .map_err(|err| format!("Unable to parse the model's caption: {}", err))
+        })?;
+    let short = reply.short.trim().trim_end_matches('.').to_string();
+    let detailed = reply.detailed.trim().to_string();
+    if short.is_empty() || detailed.is_empty() {
+        return Err("The model returned an empty caption.".into());
+    }
+
+    let caption = ImageCaption {
+        short,
+        detailed,
+        model: model.to_string(),
+        content_hash: hash,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+    store::save(&sidecar, &caption).await?;
+    Ok(caption)
+}
+
+/// `input/<id>.caption.json`.
+fn caption_path(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    resolve_library_path(dir, &format!("{id}{CAPTION_SUFFIX}"))
+        .ok_or_else(|| format!("Invalid image id: {}", id))
+}
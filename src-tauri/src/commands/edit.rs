@@ -0,0 +1,273 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+use llmapi::providers::gemini::send_generate_request_with_config;
+use llmapi::providers::openai::models::ImageEditRequest;
+use llmapi::providers::openai::send_image_edit;
+use llmapi::types::{LLMClient, LLMMessage, LLMMessageType, LLMProvider, LLMType};
+use llmapi::utils::content_hash;
+use tokio::fs;
+
+use crate::commands::generate::{
+    append_generation_log, extract_generated_image, persist_output_image, plan_output_name,
+    GenerationLogEntry,
+};
+use crate::constants::{DEFAULT_IMAGE_MODEL, DEFAULT_OPENAI_EDIT_MODEL};
+use crate::fs_utils::{ensure_input_dir, ensure_output_dir, resolve_library_path};
+use crate::image_metadata::{
+    embed_metadata, generator_name, GenerationMetadata, GenerationParameters,
+};
+use crate::image_utils::{
+    decode_image, detect_image_format, encode_image, fit_image_to_budget, image_dimensions,
+    reference_image_budget, ImageBudget, NormalizedImage,
+};
+use crate::lineage::GenerationOperation;
+use crate::llm::{parse_provider, provider_endpoint, require_api_key, resolve_model};
+use crate::mask::EditMask;
+use crate::models::{EditImageRequest, GeneratedImageResponsePayload};
+
+/// Result of a provider edit call: image bytes, MIME type and any text the
+/// provider returned alongside.
+struct EditedImage {
+    bytes: Vec<u8>,
+    mime_type: String,
+    revised_prompt: Option<String>,
+}
+
+#[tauri::command]
+pub async fn edit_image(
+    payload: EditImageRequest,
+) -> Result<GeneratedImageResponsePayload, String> {
+    let instruction = payload.instruction.trim();
+    if instruction.is_empty() {
+        return Err("Edit instruction cannot be empty.".into());
+    }
+
+    let provider = parse_provider(payload.provider.as_deref())?;
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+
+    let (library, dir) = match payload
+        .base_library
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or("output")
+    {
+        "input" => ("input", ensure_input_dir().await?),
+        "output" | "" => ("output", ensure_output_dir().await?),
+        other => return Err(format!("Unknown image library '{}'.", other)),
+    };
+
+    let base_path = resolve_library_path(&dir, &payload.base_image_id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", payload.base_image_id))?;
+    let base_bytes = fs::read(&base_path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", payload.base_image_id, err))?;
+    let base_image = decode_image(&base_bytes)?;
+
+    let mask_image = match payload
+        .mask_base64
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(data) => {
+            let bytes = BASE64_ENGINE
+                .decode(data)
+                .map_err(|err| format!("Failed to decode mask: {}", err))?;
+            Some(decode_image(&bytes).map_err(|err| format!("Invalid mask: {}", err))?)
+        }
+        None => None,
+    };
+    let mask = EditMask::from_images(&base_image, mask_image.as_ref(), payload.mask_mode)?;
+
+    let model = match provider {
+        LLMProvider::OpenAI => resolve_model(payload.model.as_deref(), DEFAULT_OPENAI_EDIT_MODEL),
+        LLMProvider::Gemini => resolve_model(payload.model.as_deref(), DEFAULT_IMAGE_MODEL),
+        LLMProvider::Anthropic => {
+            return Err("Anthropic models cannot return images; use OpenAI or Gemini.".into())
+        }
+    };
+    let output_name = plan_output_name(payload.naming.as_ref(), instruction, &model, None, 1)?;
+
+    let client = LLMClient::new(
+        provider,
+        api_key,
+        provider_endpoint(provider),
+        model.clone(),
+        LLMType::Chat,
+    );
+    let edited = match provider {
+        LLMProvider::OpenAI => {
+            edit_with_openai(&client, &base_image, &mask, instruction, &payload).await?
+        }
+        _ => edit_with_gemini(&client, &base_bytes, &mask, instruction).await?,
+    };
+
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let metadata = GenerationMetadata {
+        generator: generator_name(),
+        prompt: instruction.to_string(),
+        system_prompt: None,
+        model: model.clone(),
+        parameters: GenerationParameters {
+            size: payload.size.clone(),
+            quality: None,
+            style: None,
+        },
+        seed: None,
+        reference_hashes: vec![content_hash(&base_bytes)],
+        reference_slots: Vec::new(),
+        created_at,
+    };
+    let bytes = embed_metadata(edited.bytes.clone(), &metadata).unwrap_or_else(|err| {
+        eprintln!("Saving edited image without metadata: {err}");
+        edited.bytes
+    });
+
+    let stored_image = persist_output_image(&bytes, &edited.mime_type, &output_name).await?;
+
+    append_generation_log(GenerationLogEntry {
+        timestamp: created_at,
+        prompt: instruction.to_string(),
+        system_prompt: None,
+        reference_images: match library {
+            "input" => vec![format!("input/{}", payload.base_image_id)],
+            _ => Vec::new(),
+        },
+        reference_slots: Vec::new(),
+        output_image: format!("output/{}", stored_image.id),
+        post_process: None,
+        operation: GenerationOperation::Edit,
+        parents: match library {
+            "output" => vec![format!("output/{}", payload.base_image_id)],
+            _ => Vec::new(),
+        },
+        template: None,
+    })
+    .await?;
+
+    Ok(GeneratedImageResponsePayload {
+        image: stored_image,
+        revised_prompt: edited.revised_prompt,
+    })
+}
+
+/// Uses the native `/images/edits` endpoint with an alpha mask.
+async fn edit_with_openai(
+    client: &LLMClient,
+    base_image: &DynamicImage,
+    mask: &EditMask,
+    instruction: &str,
+    payload: &EditImageRequest,
+) -> Result<EditedImage, String> {
+    // The edits endpoint wants a PNG, which also keeps any transparency the
+    // mask was read from.
+    let budget = ImageBudget {
+        preferred_format: ImageFormat::Png,
+        ..reference_image_budget(LLMProvider::OpenAI)
+    };
+    let (base, mask) =
+        fit_base_and_mask(&encode_image(base_image, ImageFormat::Png)?, mask, &budget)?;
+    let image_mime = base.mime_type().to_string();
+    let request = ImageEditRequest {
+        image: base.bytes,
+        image_mime,
+        mask: Some(encode_image(&mask.to_alpha_mask(), ImageFormat::Png)?),
+        prompt: instruction.to_string(),
+        size: payload
+            .size
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string),
+    };
+
+    let response = send_image_edit(client, request)
+        .await
+        .map_err(|err| format!("Failed to request image edit: {}", err))?;
+
+    let data = response
+        .data
+        .into_iter()
+        .find(|item| item.b64_json.is_some())
+        .ok_or_else(|| "Provider did not return an image payload.".to_string())?;
+
+    let bytes = BASE64_ENGINE
+        .decode(data.b64_json.unwrap_or_default().trim())
+        .map_err(|err| format!("Failed to decode edited image: {}", err))?;
+    let mime_type = detect_image_format(&bytes)?.to_mime_type().to_string();
+
+    Ok(EditedImage {
+        bytes,
+        mime_type,
+        revised_prompt: data.revised_prompt,
+    })
+}
+
+/// Gemini has no mask parameter, so the mask goes along as a second image
+/// and the region is also spelled out in the instruction.
+async fn edit_with_gemini(
+    client: &LLMClient,
+    base_bytes: &[u8],
+    mask: &EditMask,
+    instruction: &str,
+) -> Result<EditedImage, String> {
+    let budget = reference_image_budget(LLMProvider::Gemini);
+    let (base, mask) = fit_base_and_mask(base_bytes, mask, &budget)?;
+    let mask_bytes = encode_image(&mask.to_reference_image(), ImageFormat::Png)?;
+
+    let prompt = format!(
+        "Edit the first image: {instruction}\n\nThe second image is a mask of the same \
+         picture. White marks the area to change; black must stay exactly as it is. {}",
+        mask.describe()
+    );
+
+    let messages = vec![LLMMessage::new(
+        None,
+        "Human",
+        vec![
+            LLMMessageType::IMAGE {
+                data_b64: BASE64_ENGINE.encode(&base.bytes),
+                file_path: Some(format!("base.{}", base.extension())),
+            },
+            LLMMessageType::IMAGE {
+                data_b64: BASE64_ENGINE.encode(&mask_bytes),
+                file_path: Some("mask.png".to_string()),
+            },
+            LLMMessageType::text(prompt),
+        ],
+    )];
+
+    let response = send_generate_request_with_config(client, messages, None)
+        .await
+        .map_err(|err| format!("Failed to request image edit: {}", err))?;
+
+    let generated = extract_generated_image(response)?;
+    let bytes = BASE64_ENGINE
+        .decode(generated.base64.trim())
+        .map_err(|err| format!("Failed to decode edited image: {}", err))?;
+
+    Ok(EditedImage {
+        bytes,
+        mime_type: generated.mime_type,
+        revised_prompt: generated.revised_prompt,
+    })
+}
+
+/// Fits the base image to `budget` and scales the mask to the same size, so
+/// the mask and its description line up with the image that is sent.
+fn fit_base_and_mask(
+    base_bytes: &[u8],
+    mask: &EditMask,
+    budget: &ImageBudget,
+) -> Result<(NormalizedImage, EditMask), String> {
+    let base = fit_image_to_budget(base_bytes, budget)?;
+    let (width, height) = image_dimensions(&base.bytes)?;
+    Ok((base, mask.resized(width, height)))
+}
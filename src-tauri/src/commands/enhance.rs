@@ -0,0 +1,121 @@
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use llmapi::types::LLMMessageType;
+use serde::Deserialize;
+
+use crate::commands::generate::reference_slot_name;
+use crate::llm::{
+    chat_image_part, default_chat_model, extract_json_object, parse_provider, require_api_key,
+    resolve_model, send_chat,
+};
+use crate::models::{EnhancePromptRequest, EnhancePromptResponse, EnhancedPrompt};
+
+const DEFAULT_OPTION_COUNT: u32 = 3;
+const MAX_OPTION_COUNT: u32 = 6;
+/// Enough for `MAX_OPTION_COUNT` detailed rewrites; the chat default of 1024
+/// cuts the JSON off after three or four.
+const ENHANCE_MAX_TOKENS: u32 = 4096;
+
+const REWRITE_INSTRUCTION: &str = "You rewrite short image-generation prompts into \
+detailed ones. Keep the user's subject and intent, and add concrete detail about \
+composition, lighting, style, colour and camera where it helps. Placeholders in braces \
+such as {img_1} refer to reference images and must be kept exactly as written. When \
+reference images are attached, use what they show to make the prompt specific.";
+
+/// The reply shape the model is asked for.
+#[derive(Deserialize)]
+struct EnhanceReply {
+    options: Vec<EnhancedPrompt>,
+}
+
+/// Asks a chat model for several rewrites of a short prompt, each with a
+/// short title and an explanation of what changed.
+#[tauri::command]
+pub async fn enhance_prompt(
+    payload: EnhancePromptRequest,
+) -> Result<EnhancePromptResponse, String> {
+    let prompt = payload.prompt.trim();
+    if prompt.is_empty() {
+        return Err("Prompt to enhance cannot be empty.".into());
+    }
+
+    let provider = parse_provider(payload.provider.as_deref())?;
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+    let model = resolve_model(payload.model.as_deref(), default_chat_model(provider));
+    let count = payload
+        .count
+        .unwrap_or(DEFAULT_OPTION_COUNT)
+        .clamp(1, MAX_OPTION_COUNT);
+
+    let instruction = payload
+        .instruction
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or(REWRITE_INSTRUCTION);
+    let instruction = format!(
+        "{instruction}\n\nReply with JSON only, no other text, in the form \
+         {{\"options\": [{{\"title\": \"...\", \"prompt\": \"...\", \"explanation\": \"...\"}}]}} \
+         with exactly {count} options. Titles are at most five words; explanations say in \
+         one or two sentences what the rewrite adds."
+    );
+
+    let mut user_content = vec![LLMMessageType::text(format!("Prompt: {prompt}"))];
+    for (index, reference) in payload.reference_images.iter().enumerate() {
+        let data = reference.data_base64.trim();
+        if data.is_empty() {
+            continue;
+        }
+
+        let slot_name = reference_slot_name(reference, index);
+        let bytes = BASE64_ENGINE
+            .decode(data)
+            .map_err(|err| format!("Failed to decode reference image '{}': {}", slot_name, err))?;
+        let image = chat_image_part(&bytes, &slot_name, provider)
+            .map_err(|err| format!("Invalid reference image '{}': {}", slot_name, err))?;
+
+        user_content.push(LLMMessageType::text(format!(
+            "Reference image {{{slot_name}}}:"
+        )));
+        user_content.push(image);
+    }
+
+    let text = send_chat(
+        provider,
+        api_key,
+        &model,
+        instruction,
+        user_content,
+        Some(ENHANCE_MAX_TOKENS),
+    )
+    .await
+    .map_err(|err| format!("Prompt enhancement failed: {}", err))?;
+
+    let options = parse_options(&text)?;
+    Ok(EnhancePromptResponse { options, model })
+}
+
+/// Reads the options from the reply, trimming each field.
+fn parse_options(text: &str) -> Result<Vec<EnhancedPrompt>, String> {
+    let json = extract_json_object(text)
+        .ok_or_else(|| format!("The model did not return any options: {}", text.trim()))?;
+
+    let reply: EnhanceReply = serde_json::from_str(json)
+        .map_err(|err| format!("Unable to parse the model's options: {}", err))?;
+
+    let options: Vec<EnhancedPrompt> = reply
+        .options
+        .into_iter()
+        .map(|option| EnhancedPrompt {
+            title: option.title.trim().to_string(),
+            prompt: option.prompt.trim().to_string(),
+            explanation: option.explanation.trim().to_string(),
+        })
+        .filter(|option| !option.prompt.is_empty())
+        .collect();
+
+    if options.is_empty() {
+        return Err("The model did not return any options.".into());
+    }
+    Ok(options)
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use image::ImageFormat;
+use tokio::fs;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::fs_utils::{
+    ensure_output_dir, ensure_unique_file_name, resolve_library_path, sanitize_file_name,
+};
+use crate::image_metadata::{
+    embed_metadata, extract_metadata, read_icc_profile, write_icc_profile,
+};
+use crate::image_utils::{decode_image, encode_image_with_quality, resize_image};
+use crate::models::{
+    ColorProfileMode, ExportImagesPayload, ExportImagesResult, ExportedImage, ResizeOptions,
+};
+
+const DEFAULT_EXPORT_QUALITY: u8 = 90;
+
+#[tauri::command]
+pub async fn export_images(payload: ExportImagesPayload) -> Result<ExportImagesResult, String> {
+    if payload.ids.is_empty() {
+        return Err("No images selected for export.".into());
+    }
+
+    let (format, extension) = parse_export_format(&payload.format)?;
+    let quality = payload.quality.unwrap_or(DEFAULT_EXPORT_QUALITY);
+
+    let destination = PathBuf::from(payload.destination_dir.trim());
+    if destination.as_os_str().is_empty() {
+        return Err("Export destination cannot be empty.".into());
+    }
+    fs::create_dir_all(&destination).await.map_err(|err| {
+        format!(
+            "Unable to create export directory '{}': {}",
+            destination.display(),
+            err
+        )
+    })?;
+
+    let output_dir = ensure_output_dir().await?;
+    let mut converted = Vec::with_capacity(payload.ids.len());
+
+    for id in &payload.ids {
+        let path = resolve_library_path(&output_dir, id)
+            .ok_or_else(|| format!("Invalid image id supplied: {}", id))?;
+        let source = fs::read(&path)
+            .await
+            .map_err(|err| format!("Unable to read image '{}': {}", id, err))?;
+
+        let options = ConvertOptions {
+            format,
+            quality,
+            lossless: payload.lossless,
+            resize: payload.resize.clone(),
+            color_profile: payload.color_profile,
+            keep_metadata: payload.keep_metadata,
+        };
+        // Decoding, resizing and encoding are CPU bound; keep them off the
+        // async runtime.
+        let bytes = tokio::task::spawn_blocking(move || convert_image(&source, &options))
+            .await
+            .map_err(|err| format!("Export task failed: {}", err))?
+            .map_err(|err| format!("Unable to export '{}': {}", id, err))?;
+
+        let stem = Path::new(id)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("image");
+        converted.push((id.clone(), format!("{stem}.{extension}"), bytes));
+    }
+
+    match payload
+        .archive_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        Some(archive_name) => write_archive(&destination, archive_name, converted).await,
+        None => write_files(&destination, converted).await,
+    }
+}
+
+fn parse_export_format(value: &str) -> Result<(ImageFormat, &'static str), String> {
+    match value.trim().to_lowercase().as_str() {
+        "png" | "image/png" => Ok((ImageFormat::Png, "png")),
+        "jpeg" | "jpg" | "image/jpeg" => Ok((ImageFormat::Jpeg, "jpg")),
+        "webp" | "image/webp" => Ok((ImageFormat::WebP, "webp")),
+        "avif" | "image/avif" => Ok((ImageFormat::Avif, "avif")),
+        other => Err(format!("Unsupported export format '{}'.", other)),
+    }
+}
+
+struct ConvertOptions {
+    format: ImageFormat,
+    quality: u8,
+    lossless: bool,
+    resize: Option<ResizeOptions>,
+    color_profile: ColorProfileMode,
+    keep_metadata: bool,
+}
+
+fn convert_image(source: &[u8], options: &ConvertOptions) -> Result<Vec<u8>, String> {
+    let mut image = decode_image(source)?;
+    if let Some(resize) = &options.resize {
+        image = resize_image(&image, resize)?;
+    }
+
+    let format = options.format;
+    let mut bytes = encode_image_with_quality(&image, format, options.quality, options.lossless)?;
+
+    // Encoders never carry the profile or our metadata over, so both are copied
+    // from the source container. AVIF output has no container support for either.
+    if format == ImageFormat::Avif {
+        return Ok(bytes);
+    }
+
+    if options.color_profile == ColorProfileMode::Keep {
+        if let Some(profile) = read_icc_profile(source)? {
+            bytes = write_icc_profile(bytes, Some(&profile))?;
+        }
+    }
+
+    if options.keep_metadata {
+        if let Some(metadata) = extract_metadata(source).unwrap_or(None) {
+            bytes = embed_metadata(bytes, &metadata)?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+async fn write_files(
+    destination: &Path,
+    converted: Vec<(String, String, Vec<u8>)>,
+) -> Result<ExportImagesResult, String> {
+    let mut files = Vec::with_capacity(converted.len());
+
+    for (id, file_name, bytes) in converted {
+        let file_name = ensure_unique_file_name(destination, &file_name).await?;
+        let path = destination.join(&file_name);
+        fs::write(&path, &bytes)
+            .await
+            .map_err(|err| format!("Unable to write '{}': {}", path.display(), err))?;
+
+        files.push(ExportedImage {
+            id,
+            file_name,
+            size: bytes.len() as u64,
+        });
+    }
+
+    Ok(ExportImagesResult {
+        files,
+        archive_path: None,
+    })
+}
+
+async fn write_archive(
+    destination: &Path,
+    archive_name: &str,
+    converted: Vec<(String, String, Vec<u8>)>,
+) -> Result<ExportImagesResult, String> {
+    let archive_name = if archive_name.to_lowercase().ends_with(".zip") {
+        archive_name.to_string()
+    } else {
+        format!("{archive_name}.zip")
+    };
+    let archive_name = sanitize_file_name(&archive_name)
+        .ok_or_else(|| format!("Invalid archive name supplied: {}", archive_name))?;
+    let archive_name = ensure_unique_file_name(destination, &archive_name).await?;
+
+    // Already-compressed image data gains nothing from deflate.
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut used_names = HashSet::new();
+    let mut files = Vec::with_capacity(converted.len());
+
+    for (id, file_name, bytes) in converted {
+        let file_name = unique_entry_name(&mut used_names, &file_name);
+        writer
+            .start_file(file_name.as_str(), options)
+            .and_then(|_| writer.write_all(&bytes).map_err(Into::into))
+            .map_err(|err| format!("Unable to add '{}' to archive: {}", file_name, err))?;
+
+        files.push(ExportedImage {
+            id,
+            file_name,
+            size: bytes.len() as u64,
+        });
+    }
+
+    let archive = writer
+        .finish()
+        .map_err(|err| format!("Unable to finish archive: {}", err))?
+        .into_inner();
+
+    let path = destination.join(&archive_name);
+    fs::write(&path, archive)
+        .await
+        .map_err(|err| format!("Unable to write '{}': {}", path.display(), err))?;
+
+    Ok(ExportImagesResult {
+        files,
+        archive_path: Some(path.to_string_lossy().into_owned()),
+    })
+}
+
+fn unique_entry_name(used: &mut HashSet<String>, file_name: &str) -> String {
+    if used.insert(file_name.to_string()) {
+        return file_name.to_string();
+    }
+
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("image");
+    let extension = path.extension().and_then(|ext| ext.to_str());
+
+    let mut counter = 1;
+    loop {
+        let candidate = match extension {
+            Some(ext) => format!("{stem}-{counter}.{ext}"),
+            None => format!("{stem}-{counter}"),
+        };
+        if used.insert(candidate.clone()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
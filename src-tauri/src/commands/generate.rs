@@ -2,157 +2,198 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
 use base64::Engine;
+use chrono::Local;
+use image::ImageFormat;
 use llmapi::providers::gemini::models::GeminiResponse;
-use llmapi::providers::gemini::send_generate_request;
+use llmapi::providers::gemini::{
+    send_generate_request_with_config, upload_file_cached, FileUriCache,
+};
 use llmapi::types::{LLMClient, LLMMessage, LLMMessageType, LLMProvider, LLMType, LLMUserType};
+use llmapi::utils::content_hash;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tokio::fs;
 use tokio::fs::try_exists;
 
-use crate::constants::{DEFAULT_GEMINI_ENDPOINT, DEFAULT_IMAGE_MIME, DEFAULT_IMAGE_MODEL};
+use crate::commands::prompt_versions::resolve_template_ref;
+use crate::commands::prompts::{default_system_prompt, record_template_usage};
+use crate::constants::{
+    DEFAULT_GEMINI_ENDPOINT, DEFAULT_IMAGE_MIME, DEFAULT_IMAGE_MODEL, GEMINI_FILE_CACHE_FILE,
+    GEMINI_FILE_UPLOAD_THRESHOLD,
+};
 use crate::fs_utils::{
-    build_stored_image, default_extension_for_mime, ensure_output_dir, ensure_unique_file_name,
+    build_stored_image, default_extension_for_mime, ensure_cache_dir, ensure_output_dir,
+    write_unique_file,
+};
+use crate::image_metadata::{
+    embed_metadata, generator_name, GenerationMetadata, GenerationParameters, ReferenceSlot,
+};
+use crate::image_utils::{
+    decode_image, detect_image_format, encode_image, fit_image_to_budget, reference_image_budget,
+    ImageBudget, NormalizedImage,
 };
-use crate::models::{GenerateImageRequest, GeneratedImage, GeneratedImageResponsePayload};
+use crate::lineage::{record_lineage, GenerationOperation, LineageNode};
+use crate::llm::{require_api_key, resolve_model};
+use crate::models::{
+    GenerateImageRequest, GeneratedImage, GeneratedImageResponsePayload, OutputNamingOptions,
+    PostProcessOptions, PromptTemplateRef, ReferenceImagePayload, StoredImage,
+};
+use crate::naming::{build_output_name, NamingContext, OutputName};
+use crate::post_process::{apply_post_process, validate_post_process};
+use crate::store;
 
 #[tauri::command]
 pub async fn generate_image(
     payload: GenerateImageRequest,
+) -> Result<GeneratedImageResponsePayload, String> {
+    run_generation(payload, GenerationOperation::Generate, 1, None).await
+}
+
+/// Runs one generation end to end: builds the messages, calls the provider,
+/// post-processes, embeds metadata, saves the output and records history.
+/// `index` feeds the `{index}` naming token. `instruction` is sent as an extra
+/// text part after the prompt but is not recorded, so history, lineage and
+/// embedded metadata keep the user's own prompt.
+pub(crate) async fn run_generation(
+    mut payload: GenerateImageRequest,
+    operation: GenerationOperation,
+    index: usize,
+    instruction: Option<String>,
 ) -> Result<GeneratedImageResponsePayload, String> {
     if payload.image_prompt.trim().is_empty() {
         return Err("Image prompt cannot be empty".into());
     }
 
-    let api_key = payload
-        .api_key
-        .as_deref()
-        .map(str::trim)
-        .filter(|value| !value.is_empty())
-        .ok_or_else(|| "API key is required to generate images.".to_string())?;
+    apply_default_system_prompt(&mut payload).await;
 
-    let model_name = payload
-        .model
-        .as_deref()
-        .map(str::trim)
-        .filter(|value| !value.is_empty())
-        .unwrap_or(DEFAULT_IMAGE_MODEL);
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+    let trimmed_model = resolve_model(payload.model.as_deref(), DEFAULT_IMAGE_MODEL);
+
+    let input = build_generation_input(&payload);
+    input.validate()?;
+    if let Some(options) = payload.post_process.as_ref() {
+        validate_post_process(options)
+            .map_err(|err| format!("Invalid post-processing options: {}", err))?;
+    }
 
-    let trimmed_model = model_name
-        .strip_prefix("models/")
-        .unwrap_or(model_name)
-        .to_string();
+    let output_name = plan_output_name(
+        payload.naming.as_ref(),
+        payload.image_prompt.trim(),
+        &trimmed_model,
+        payload.seed,
+        index,
+    )?;
+
+    let client = LLMClient::new(
+        LLMProvider::Gemini,
+        api_key,
+        DEFAULT_GEMINI_ENDPOINT,
+        trimmed_model.clone(),
+        LLMType::Chat,
+    );
 
     let mut messages: Vec<LLMMessage> = Vec::new();
 
-    if let Some(system_prompt) = payload
-        .system_prompt
-        .as_deref()
-        .map(str::trim)
-        .filter(|value| !value.is_empty())
-    {
+    if let Some(system_prompt) = input.system_prompt {
         messages.push(LLMMessage::new(
             None,
             "Human",
-            vec![LLMMessageType::text(system_prompt.to_string())],
+            vec![LLMMessageType::text(system_prompt)],
         ));
     }
 
-    let mut user_content: Vec<LLMMessageType> = Vec::new();
-
-    for (index, reference) in payload.reference_images.iter().enumerate() {
-        let data = reference.data_base64.trim();
-        if data.is_empty() {
-            continue;
-        }
-
-        let mime_type = reference
-            .mime_type
-            .as_deref()
-            .map(str::trim)
-            .filter(|value| !value.is_empty())
-            .unwrap_or(DEFAULT_IMAGE_MIME);
-
-        let extension = default_extension_for_mime(mime_type).unwrap_or_else(|| "bin".to_string());
-        let slot_name = reference
-            .slot
-            .as_deref()
-            .map(str::trim)
-            .filter(|value| !value.is_empty())
-            .map(|value| value.to_string())
-            .unwrap_or_else(|| format!("reference_{index}"));
-
-        let pseudo_path = if extension.is_empty() {
-            slot_name
-        } else {
-            format!("{slot_name}.{extension}")
-        };
-
-        user_content.push(LLMMessageType::IMAGE {
-            data_b64: data.to_string(),
-            file_path: Some(pseudo_path),
-        });
-    }
+    let reference_hashes: Vec<String> = input
+        .references
+        .iter()
+        .map(|reference| reference.original_hash.clone())
+        .collect();
+    let reference_slots: Vec<ReferenceSlot> = input
+        .references
+        .iter()
+        .map(|reference| ReferenceSlot {
+            slot: reference.slot_name.clone(),
+            hash: reference.original_hash.clone(),
+        })
+        .collect();
+    let mut user_content = reference_parts(&client, input.references).await?;
 
-    let user_prompt = build_user_prompt(&payload);
-    if !user_prompt.trim().is_empty() {
-        user_content.push(LLMMessageType::text(user_prompt));
+    if !input.user_prompt.trim().is_empty() {
+        user_content.push(LLMMessageType::text(input.user_prompt));
     }
-
-    if user_content.is_empty() {
-        return Err("A prompt or reference image is required to generate content.".into());
+    if let Some(instruction) = instruction.filter(|value| !value.trim().is_empty()) {
+        user_content.push(LLMMessageType::text(instruction));
     }
 
     messages.push(LLMMessage::new(None, "Human", user_content));
 
-    let client = LLMClient::new(
-        LLMProvider::Gemini,
-        api_key,
-        DEFAULT_GEMINI_ENDPOINT,
-        trimmed_model.clone(),
-        LLMType::Chat,
-    );
-
     log_generate_payload(api_key, &trimmed_model, &messages)
         .await
         .map_err(|err| format!("Failed to write debug log: {}", err))?;
 
-    let response = send_generate_request(&client, messages.clone())
+    let generation_config = payload.seed.map(|seed| json!({ "seed": seed }));
+    let response = send_generate_request_with_config(&client, messages.clone(), generation_config)
         .await
         .map_err(|err| format!("Failed to request image generation: {}", err))?;
 
     let generated = extract_generated_image(response)?;
 
-    let output_dir = ensure_output_dir().await?;
     let bytes = BASE64_ENGINE
         .decode(generated.base64.trim())
         .map_err(|err| format!("Failed to decode generated image: {}", err))?;
 
-    let extension =
-        default_extension_for_mime(&generated.mime_type).unwrap_or_else(|| "bin".to_string());
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis();
-    let base_name = if extension.is_empty() {
-        format!("image_{timestamp}")
-    } else {
-        format!("image_{timestamp}.{}", extension)
+    let (bytes, mime_type) = match payload.post_process.as_ref() {
+        Some(options) => {
+            let processed = post_process_output(&bytes, options)
+                .map_err(|err| format!("Failed to post-process generated image: {}", err))?;
+            let mime_type = processed.mime_type().to_string();
+            (processed.bytes, mime_type)
+        }
+        None => (bytes, generated.mime_type.clone()),
     };
 
-    let unique_name = ensure_unique_file_name(&output_dir, &base_name).await?;
-    let target_path = output_dir.join(&unique_name);
-
-    fs::write(&target_path, &bytes)
-        .await
-        .map_err(|err| format!("Unable to persist generated image: {}", err))?;
+    let metadata = GenerationMetadata {
+        generator: generator_name(),
+        prompt: payload.image_prompt.trim().to_string(),
+        system_prompt: payload
+            .system_prompt
+            .as_ref()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty()),
+        model: trimmed_model.clone(),
+        parameters: GenerationParameters {
+            size: payload.size.clone(),
+            quality: payload.quality.clone(),
+            style: payload.style.clone(),
+        },
+        seed: payload.seed,
+        reference_hashes,
+        reference_slots,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    };
+    let bytes = embed_metadata(bytes.clone(), &metadata).unwrap_or_else(|err| {
+        eprintln!("Saving generated image without metadata: {err}");
+        bytes
+    });
 
-    let stored_image = build_stored_image(
-        &target_path,
-        bytes.len() as u64,
-        Some(generated.mime_type.clone()),
-    )
-    .await?;
+    let stored_image = persist_output_image(&bytes, &mime_type, &output_name).await?;
 
+    // Library references are logged with the slot each was sent under, so a
+    // branch from this output can restore them.
+    let (reference_images, reference_slots): (Vec<String>, Vec<String>) = payload
+        .reference_images
+        .iter()
+        .enumerate()
+        .filter_map(|(index, reference)| {
+            let name = reference.file_name.as_ref()?;
+            Some((
+                format!("input/{name}"),
+                reference_slot_name(reference, index),
+            ))
+        })
+        .unzip();
     append_generation_log(GenerationLogEntry {
         timestamp: SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -164,22 +205,94 @@ pub async fn generate_image(
             .as_ref()
             .map(|value| value.trim().to_string())
             .filter(|value| !value.is_empty()),
-        reference_images: payload
-            .reference_images
+        reference_images,
+        reference_slots,
+        output_image: format!("output/{}", stored_image.id),
+        post_process: payload.post_process.clone(),
+        operation,
+        parents: payload
+            .parent_ids
             .iter()
-            .filter_map(|reference| reference.file_name.as_ref())
-            .map(|name| format!("input/{name}"))
+            .map(|id| format!("output/{id}"))
             .collect(),
-        output_image: format!("output/{}", stored_image.name.clone()),
+        template: resolve_template_ref(payload.template.as_ref()).await,
     })
     .await?;
 
+    if let Some(template) = &payload.template {
+        if let Err(err) = record_template_usage(&template.id).await {
+            eprintln!("Unable to record template usage: {err}");
+        }
+    }
+
     Ok(GeneratedImageResponsePayload {
         image: stored_image,
         revised_prompt: generated.revised_prompt,
     })
 }
 
+/// Renders the output name for a request. Done before the provider call so a
+/// bad naming template never costs a generation.
+pub(crate) fn plan_output_name(
+    naming: Option<&OutputNamingOptions>,
+    prompt: &str,
+    model: &str,
+    seed: Option<u32>,
+    index: usize,
+) -> Result<OutputName, String> {
+    build_output_name(
+        naming,
+        &NamingContext {
+            project: naming.and_then(|options| options.project.as_deref()),
+            template_name: naming.and_then(|options| options.template_name.as_deref()),
+            prompt,
+            model,
+            seed,
+            index,
+            created_at: Local::now(),
+        },
+    )
+}
+
+/// Writes a finished output image under its planned name, returning it with
+/// its `/`-separated library id.
+pub(crate) async fn persist_output_image(
+    bytes: &[u8],
+    mime_type: &str,
+    name: &OutputName,
+) -> Result<StoredImage, String> {
+    let output_dir = ensure_output_dir().await?;
+    let extension = default_extension_for_mime(mime_type).unwrap_or_else(|| "bin".to_string());
+    let target_dir = name
+        .subfolder
+        .iter()
+        .fold(output_dir, |dir, segment| dir.join(segment));
+    fs::create_dir_all(&target_dir)
+        .await
+        .map_err(|err| format!("Unable to create output folder: {}", err))?;
+
+    let unique_name = write_unique_file(&target_dir, &name.file_name(&extension), bytes)
+        .await
+        .map_err(|err| format!("Unable to persist generated image: {}", err))?;
+    let target_path = target_dir.join(&unique_name);
+    let relative_id = name
+        .subfolder
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::once(unique_name.as_str()))
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut stored_image = build_stored_image(
+        &target_path,
+        bytes.len() as u64,
+        Some(mime_type.to_string()),
+    )
+    .await?;
+    stored_image.id = relative_id;
+    Ok(stored_image)
+}
+
 const LOG_FILE_NAME: &str = "log.json";
 const MAX_LOG_ENTRIES: usize = 50;
 
@@ -190,56 +303,328 @@ pub struct GenerationLogEntry {
     pub prompt: String,
     pub system_prompt: Option<String>,
     pub reference_images: Vec<String>,
+    /// Slot of each entry in `reference_images`, by position. Empty for
+    /// entries recorded before slots were.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference_slots: Vec<String>,
     pub output_image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_process: Option<PostProcessOptions>,
+    #[serde(default)]
+    pub operation: GenerationOperation,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<PromptTemplateRef>,
 }
 
-async fn append_generation_log(entry: GenerationLogEntry) -> Result<(), String> {
-    let dir = ensure_output_dir().await?;
-    let path = dir.join(LOG_FILE_NAME);
+/// Appends to the capped history log and records the entry in the lineage
+/// store.
+pub(crate) async fn append_generation_log(entry: GenerationLogEntry) -> Result<(), String> {
+    record_lineage(LineageNode {
+        image: entry.output_image.clone(),
+        operation: entry.operation,
+        parents: entry.parents.clone(),
+        references: entry.reference_images.clone(),
+        reference_slots: entry.reference_slots.clone(),
+        prompt: entry.prompt.clone(),
+        system_prompt: entry.system_prompt.clone(),
+        timestamp: entry.timestamp,
+    })
+    .await?;
 
-    let mut entries: Vec<GenerationLogEntry> = if try_exists(&path)
-        .await
-        .map_err(|err| format!("Failed to check log file: {}", err))?
-    {
-        let contents = fs::read_to_string(&path)
-            .await
-            .unwrap_or_else(|_| "[]".to_string());
-        serde_json::from_str(&contents).unwrap_or_default()
-    } else {
-        Vec::new()
-    };
+    let dir = ensure_output_dir().await?;
+    let mut entries = store::open::<Vec<GenerationLogEntry>>(&dir.join(LOG_FILE_NAME)).await?;
 
     entries.push(entry);
     if entries.len() > MAX_LOG_ENTRIES {
-        entries = entries.split_off(entries.len() - MAX_LOG_ENTRIES);
+        let excess = entries.len() - MAX_LOG_ENTRIES;
+        entries.drain(..excess);
     }
+    entries.save().await
+}
 
-    let payload = serde_json::to_string_pretty(&entries)
-        .map_err(|err| format!("Unable to serialise generation logs: {}", err))?;
-
-    fs::write(&path, payload)
-        .await
-        .map_err(|err| format!("Failed to write generation log: {}", err))
+/// Records an output derived locally from `source_id`, without a provider
+/// call, reusing the source's prompt so history and lineage stay readable.
+pub(crate) async fn record_derived_output(
+    source_id: &str,
+    output_id: &str,
+    operation: GenerationOperation,
+    source_metadata: Option<&GenerationMetadata>,
+) -> Result<(), String> {
+    append_generation_log(GenerationLogEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        prompt: source_metadata
+            .map(|metadata| metadata.prompt.clone())
+            .unwrap_or_default(),
+        system_prompt: source_metadata.and_then(|metadata| metadata.system_prompt.clone()),
+        reference_images: Vec::new(),
+        reference_slots: Vec::new(),
+        output_image: format!("output/{output_id}"),
+        post_process: None,
+        operation,
+        parents: vec![format!("output/{source_id}")],
+        template: None,
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn list_generation_logs() -> Result<Vec<GenerationLogEntry>, String> {
     let dir = ensure_output_dir().await?;
-    let path = dir.join(LOG_FILE_NAME);
+    store::load(&dir.join(LOG_FILE_NAME)).await
+}
+
+async fn load_file_cache() -> FileUriCache {
+    let path = match ensure_cache_dir().await {
+        Ok(dir) => dir.join(GEMINI_FILE_CACHE_FILE),
+        Err(err) => {
+            eprintln!("Gemini file cache unavailable: {err}");
+            return FileUriCache::default();
+        }
+    };
 
-    if !try_exists(&path)
+    store::load(&path).await.unwrap_or_else(|err| {
+        eprintln!("Ignoring unreadable Gemini file cache: {err}");
+        FileUriCache::default()
+    })
+}
+
+/// Merges this generation's uploads into the cache on disk, so concurrent
+/// generations don't drop each other's entries.
+async fn save_file_cache(cache: FileUriCache) {
+    let result = async {
+        let dir = ensure_cache_dir().await?;
+        let mut stored = store::open::<FileUriCache>(&dir.join(GEMINI_FILE_CACHE_FILE)).await?;
+        stored.merge(cache);
+        stored.prune_expired();
+        stored.save().await
+    }
+    .await;
+
+    if let Err(err) = result {
+        eprintln!("Failed to persist Gemini file cache: {err}");
+    }
+}
+
+/// Runs the request's post-processing and re-encodes in the provider's format
+/// where possible, falling back to PNG.
+fn post_process_output(
+    bytes: &[u8],
+    options: &PostProcessOptions,
+) -> Result<NormalizedImage, String> {
+    let format = match detect_image_format(bytes)? {
+        format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP) => format,
+        _ => ImageFormat::Png,
+    };
+
+    let image = apply_post_process(decode_image(bytes)?, options)?;
+    Ok(NormalizedImage {
+        bytes: encode_image(&image, format)?,
+        format,
+    })
+}
+
+/// A reference image decoded and fitted to the provider's budget.
+pub(crate) struct PreparedReference {
+    pub slot_name: String,
+    pub image: NormalizedImage,
+    pub original_hash: String,
+}
+
+impl PreparedReference {
+    /// Larger references are uploaded through the Files API instead of being
+    /// sent inline.
+    pub fn needs_upload(&self) -> bool {
+        self.image.bytes.len() > GEMINI_FILE_UPLOAD_THRESHOLD
+    }
+
+    fn inline_part(&self) -> LLMMessageType {
+        LLMMessageType::IMAGE {
+            data_b64: BASE64_ENGINE.encode(&self.image.bytes),
+            file_path: Some(format!("{}.{}", self.slot_name, self.image.extension())),
+        }
+    }
+}
+
+/// What a generation request sends, before large references are uploaded.
+/// Shared by `run_generation` and preflight checks so both see the same
+/// messages.
+pub(crate) struct GenerationInput {
+    pub system_prompt: Option<String>,
+    pub references: Vec<PreparedReference>,
+    /// Errors for references that could not be decoded.
+    pub invalid_references: Vec<String>,
+    pub user_prompt: String,
+}
+
+impl GenerationInput {
+    /// The first problem that stops the request from being sent.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(err) = self.invalid_references.first() {
+            return Err(err.clone());
+        }
+        if self.references.is_empty() && self.user_prompt.trim().is_empty() {
+            return Err("A prompt or reference image is required to generate content.".into());
+        }
+        Ok(())
+    }
+}
+
+/// Uploads the request's large references ahead of time, so parallel runs of
+/// the same request find them in the file cache instead of each uploading its
+/// own copy.
+pub(crate) async fn upload_references(payload: &GenerateImageRequest) -> Result<(), String> {
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+    let input = build_generation_input(payload);
+    input.validate()?;
+
+    let uploads: Vec<PreparedReference> = input
+        .references
+        .into_iter()
+        .filter(PreparedReference::needs_upload)
+        .collect();
+    if uploads.is_empty() {
+        return Ok(());
+    }
+
+    let client = LLMClient::new(
+        LLMProvider::Gemini,
+        api_key,
+        DEFAULT_GEMINI_ENDPOINT,
+        resolve_model(payload.model.as_deref(), DEFAULT_IMAGE_MODEL),
+        LLMType::Chat,
+    );
+    reference_parts(&client, uploads).await.map(|_| ())
+}
+
+/// Message parts for the references: small ones inline, larger ones as Files
+/// API uploads, reusing cached uploads of the same content.
+async fn reference_parts(
+    client: &LLMClient,
+    references: Vec<PreparedReference>,
+) -> Result<Vec<LLMMessageType>, String> {
+    let mut parts = Vec::new();
+    let mut file_cache: Option<FileUriCache> = None;
+
+    for reference in references {
+        if !reference.needs_upload() {
+            parts.push(reference.inline_part());
+            continue;
+        }
+
+        if file_cache.is_none() {
+            file_cache = Some(load_file_cache().await);
+        }
+        let cache = file_cache.as_mut().expect("file cache loaded above");
+
+        let uploaded = upload_file_cached(
+            client,
+            cache,
+            reference.image.bytes,
+            reference.image.format.to_mime_type(),
+            &reference.slot_name,
+        )
         .await
-        .map_err(|err| format!("Failed to check log file: {}", err))?
+        .map_err(|err| {
+            format!(
+                "Failed to upload reference '{}': {}",
+                reference.slot_name, err
+            )
+        })?;
+
+        parts.push(uploaded.to_message_part());
+    }
+
+    if let Some(cache) = file_cache {
+        save_file_cache(cache).await;
+    }
+
+    Ok(parts)
+}
+
+/// Fills an empty system prompt from the saved default.
+pub(crate) async fn apply_default_system_prompt(payload: &mut GenerateImageRequest) {
+    if payload
+        .system_prompt
+        .as_deref()
+        .is_none_or(|value| value.trim().is_empty())
     {
-        return Ok(Vec::new());
+        payload.system_prompt = default_system_prompt().await;
     }
+}
 
-    let contents = fs::read_to_string(&path)
-        .await
-        .map_err(|err| format!("Unable to read generation log: {}", err))?;
+pub(crate) fn build_generation_input(payload: &GenerateImageRequest) -> GenerationInput {
+    let reference_budget = reference_image_budget(LLMProvider::Gemini);
+    let mut references = Vec::new();
+    let mut invalid_references = Vec::new();
+
+    for (index, reference) in payload.reference_images.iter().enumerate() {
+        let data = reference.data_base64.trim();
+        if data.is_empty() {
+            continue;
+        }
+
+        let slot_name = reference_slot_name(reference, index);
+        match prepare_reference_image(data, &slot_name, &reference_budget) {
+            Ok((image, original_hash)) => references.push(PreparedReference {
+                slot_name,
+                image,
+                original_hash,
+            }),
+            Err(err) => invalid_references.push(err),
+        }
+    }
 
-    serde_json::from_str(&contents)
-        .map_err(|err| format!("Unable to parse generation log: {}", err))
+    GenerationInput {
+        system_prompt: payload
+            .system_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string),
+        references,
+        invalid_references,
+        user_prompt: build_user_prompt(payload),
+    }
+}
+
+/// The reference's slot, or `reference_<index>` when it has none.
+pub(crate) fn reference_slot_name(reference: &ReferenceImagePayload, index: usize) -> String {
+    reference
+        .slot
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| format!("reference_{index}"))
+}
+
+/// Returns the image to send along with the content hash of the original.
+fn prepare_reference_image(
+    data_base64: &str,
+    slot_name: &str,
+    budget: &ImageBudget,
+) -> Result<(NormalizedImage, String), String> {
+    let original = BASE64_ENGINE
+        .decode(data_base64)
+        .map_err(|err| format!("Failed to decode reference image '{}': {}", slot_name, err))?;
+
+    let prepared = fit_image_to_budget(&original, budget)
+        .map_err(|err| format!("Invalid reference image '{}': {}", slot_name, err))?;
+
+    eprintln!(
+        "Reference '{}': original {} bytes, sending {} bytes as {}",
+        slot_name,
+        original.len(),
+        prepared.bytes.len(),
+        prepared.mime_type()
+    );
+
+    Ok((prepared, content_hash(&original)))
 }
 
 fn build_user_prompt(payload: &GenerateImageRequest) -> String {
@@ -295,7 +680,7 @@ fn build_user_prompt(payload: &GenerateImageRequest) -> String {
     sections.join("\n\n")
 }
 
-fn extract_generated_image(response: GeminiResponse) -> Result<GeneratedImage, String> {
+pub(crate) fn extract_generated_image(response: GeminiResponse) -> Result<GeneratedImage, String> {
     for candidate in response.candidates {
         let mut first_text: Option<String> = None;
 
@@ -395,6 +780,13 @@ async fn log_generate_payload(
                             data_length: Some(data_b64.len()),
                         }
                     }
+                    LLMMessageType::FILE { file_uri, .. } => DebugMessageContent {
+                        kind: "file",
+                        text: None,
+                        file_path: Some(file_uri.clone()),
+                        data_preview: None,
+                        data_length: None,
+                    },
                 })
                 .collect();
 
@@ -1,11 +1,15 @@
 use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
 use base64::Engine;
+use std::path::Path;
 use tokio::fs;
 
+use crate::commands::captions::remove_captions;
+use crate::constants::DEFAULT_MAX_UPLOAD_DIMENSION;
 use crate::fs_utils::{
     collect_directory_images, delete_from_directory, do_open_dir, ensure_input_dir,
-    ensure_output_dir, ensure_unique_file_name, resolve_mime_type, sanitize_file_name,
+    ensure_output_dir, ensure_unique_file_name, sanitize_file_name,
 };
+use crate::image_utils::{normalize_image, parse_output_format};
 use crate::models::{StoredImage, UploadImagePayload};
 
 #[tauri::command]
@@ -21,7 +25,7 @@ pub fn open_dir(path: &str) -> Result<(), String> {
 #[tauri::command]
 pub async fn list_images() -> Result<Vec<StoredImage>, String> {
     let input_dir = ensure_input_dir().await?;
-    collect_directory_images(&input_dir).await
+    collect_directory_images(&input_dir, false).await
 }
 
 #[tauri::command]
@@ -36,33 +40,44 @@ pub async fn upload_images(payloads: Vec<UploadImagePayload>) -> Result<Vec<Stor
     for payload in payloads {
         let UploadImagePayload {
             file_name,
-            mime_type,
+            mime_type: _,
             data_base64,
+            output_format,
+            max_dimension,
         } = payload;
 
         let sanitized_name = sanitize_file_name(&file_name)
             .ok_or_else(|| format!("Invalid file name supplied: {}", file_name))?;
 
-        let unique_name = ensure_unique_file_name(&input_dir, &sanitized_name).await?;
-        let target_path = input_dir.join(&unique_name);
-
-        let trimmed_base64 = data_base64.trim();
         let data = BASE64_ENGINE
-            .decode(trimmed_base64)
+            .decode(data_base64.trim())
             .map_err(|err| format!("Failed to decode image '{}': {}", file_name, err))?;
 
-        fs::write(&target_path, &data)
+        let output_format = parse_output_format(output_format.as_deref())?;
+        let normalized = normalize_image(
+            &data,
+            max_dimension.unwrap_or(DEFAULT_MAX_UPLOAD_DIMENSION),
+            output_format,
+        )
+        .map_err(|err| format!("Rejected upload '{}': {}", file_name, err))?;
+
+        let normalized_name = Path::new(&sanitized_name)
+            .with_extension(normalized.extension())
+            .to_string_lossy()
+            .into_owned();
+        let unique_name = ensure_unique_file_name(&input_dir, &normalized_name).await?;
+        let target_path = input_dir.join(&unique_name);
+
+        fs::write(&target_path, &normalized.bytes)
             .await
             .map_err(|err| format!("Unable to write file '{}': {}", unique_name, err))?;
 
-        let resolved_mime = resolve_mime_type(mime_type, &target_path);
-
         stored_images.push(StoredImage {
             id: unique_name.clone(),
             name: unique_name,
-            size: data.len() as u64,
-            mime_type: resolved_mime,
-            base64: trimmed_base64.to_string(),
+            size: normalized.bytes.len() as u64,
+            mime_type: normalized.mime_type().to_string(),
+            base64: BASE64_ENGINE.encode(&normalized.bytes),
         });
     }
 
@@ -71,13 +86,14 @@ pub async fn upload_images(payloads: Vec<UploadImagePayload>) -> Result<Vec<Stor
 
 #[tauri::command]
 pub async fn delete_images(ids: Vec<String>) -> Result<(), String> {
+    remove_captions(&ids).await?;
     delete_from_directory(ids, ensure_input_dir().await?).await
 }
 
 #[tauri::command]
 pub async fn list_output_images() -> Result<Vec<StoredImage>, String> {
     let output_dir = ensure_output_dir().await?;
-    collect_directory_images(&output_dir).await
+    collect_directory_images(&output_dir, true).await
 }
 
 #[tauri::command]
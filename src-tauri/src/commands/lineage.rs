@@ -0,0 +1,133 @@
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::commands::generate::list_generation_logs;
+use crate::fs_utils::{
+    ensure_input_dir, ensure_output_dir, resolve_library_path, resolve_mime_type,
+};
+use crate::image_metadata::extract_metadata;
+use crate::lineage::{ancestors_of, descendants_of, load_lineage, LineageNode, LineageTree};
+use crate::models::{GenerateImageRequest, ReferenceImagePayload};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageLineage {
+    pub ancestors: Vec<LineageNode>,
+    pub tree: LineageTree,
+}
+
+#[tauri::command]
+pub async fn get_image_lineage(id: String) -> Result<ImageLineage, String> {
+    let nodes = load_lineage_with_history().await?;
+    let node = find_node(&nodes, &id)?;
+
+    Ok(ImageLineage {
+        ancestors: ancestors_of(&nodes, &node.image),
+        tree: descendants_of(&nodes, node),
+    })
+}
+
+/// Prefills a generation request from any output so a new branch can grow
+/// from it: the node's prompt, settings and input references, plus the image
+/// itself as a reference and as the parent of whatever is generated next.
+#[tauri::command]
+pub async fn branch_from_image(id: String) -> Result<GenerateImageRequest, String> {
+    build_branch_request(&id).await
+}
+
+pub(crate) async fn build_branch_request(id: &str) -> Result<GenerateImageRequest, String> {
+    let nodes = load_lineage_with_history().await?;
+    let node = find_node(&nodes, id)?;
+    let output_id = node.image.strip_prefix("output/").unwrap_or(&node.image);
+
+    let output_path = resolve_library_path(&ensure_output_dir().await?, output_id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", output_id))?;
+    let image_bytes = fs::read(&output_path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", output_id, err))?;
+    let metadata = extract_metadata(&image_bytes).unwrap_or(None);
+
+    let input_dir = ensure_input_dir().await?;
+    let mut reference_images = Vec::new();
+    for (index, reference) in node.references.iter().enumerate() {
+        let Some(name) = reference.strip_prefix("input/") else {
+            continue;
+        };
+        let Some(path) = resolve_library_path(&input_dir, name) else {
+            continue;
+        };
+        // References deleted since the original run are simply left out.
+        let Ok(bytes) = fs::read(&path).await else {
+            continue;
+        };
+        reference_images.push(ReferenceImagePayload {
+            mime_type: Some(resolve_mime_type(None, &path)),
+            data_base64: BASE64_ENGINE.encode(bytes),
+            slot: node.reference_slots.get(index).cloned(),
+            file_name: Some(name.to_string()),
+        });
+    }
+    reference_images.push(ReferenceImagePayload {
+        mime_type: Some(resolve_mime_type(None, &output_path)),
+        data_base64: BASE64_ENGINE.encode(&image_bytes),
+        slot: Some("branch_source".to_string()),
+        file_name: None,
+    });
+
+    let parameters = metadata
+        .as_ref()
+        .map(|metadata| metadata.parameters.clone())
+        .unwrap_or_default();
+
+    Ok(GenerateImageRequest {
+        api_key: None,
+        model: metadata.as_ref().map(|metadata| metadata.model.clone()),
+        system_prompt: node.system_prompt.clone(),
+        image_prompt: node.prompt.clone(),
+        reference_images,
+        size: parameters.size,
+        quality: parameters.quality,
+        style: parameters.style,
+        user: None,
+        seed: metadata.and_then(|metadata| metadata.seed),
+        naming: None,
+        post_process: None,
+        parent_ids: vec![output_id.to_string()],
+        template: None,
+    })
+}
+
+/// Lineage nodes plus history entries that predate the lineage store, which
+/// become root nodes.
+async fn load_lineage_with_history() -> Result<Vec<LineageNode>, String> {
+    let mut nodes = load_lineage().await?;
+
+    for entry in list_generation_logs().await? {
+        if nodes.iter().any(|node| node.image == entry.output_image) {
+            continue;
+        }
+        nodes.push(LineageNode {
+            image: entry.output_image,
+            operation: entry.operation,
+            parents: entry.parents,
+            references: entry.reference_images,
+            reference_slots: entry.reference_slots,
+            prompt: entry.prompt,
+            system_prompt: entry.system_prompt,
+            timestamp: entry.timestamp,
+        });
+    }
+
+    Ok(nodes)
+}
+
+fn find_node(nodes: &[LineageNode], id: &str) -> Result<LineageNode, String> {
+    let image = format!("output/{}", id.strip_prefix("output/").unwrap_or(id));
+    nodes
+        .iter()
+        .find(|node| node.image == image)
+        .cloned()
+        .ok_or_else(|| format!("No generation history recorded for '{}'.", id))
+}
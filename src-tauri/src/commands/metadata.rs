@@ -0,0 +1,224 @@
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use llmapi::utils::content_hash;
+use tokio::fs;
+
+use crate::fs_utils::{
+    ensure_input_dir, ensure_output_dir, resolve_library_path, resolve_mime_type,
+};
+use crate::image_metadata::{
+    extract_metadata, parse_foreign_generation, read_png_text_chunks, ForeignGeneration,
+    GenerationMetadata,
+};
+use crate::image_utils::detect_image_format;
+use crate::models::{
+    GenerateImageRequest, ImportImageMetadataPayload, ImportedGeneration, ReferenceImagePayload,
+    SavePromptsPayload,
+};
+
+#[tauri::command]
+pub async fn read_image_metadata(id: String) -> Result<Option<GenerationMetadata>, String> {
+    let path = resolve_library_path(&ensure_output_dir().await?, &id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", id))?;
+
+    let bytes = fs::read(&path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", id, err))?;
+
+    extract_metadata(&bytes)
+}
+
+#[tauri::command]
+pub async fn import_image_metadata(
+    payload: ImportImageMetadataPayload,
+) -> Result<ImportedGeneration, String> {
+    let bytes = BASE64_ENGINE
+        .decode(payload.data_base64.trim())
+        .map_err(|err| format!("Failed to decode image: {}", err))?;
+
+    let template_name = payload
+        .file_name
+        .as_deref()
+        .and_then(|name| Path::new(name).file_stem())
+        .and_then(|stem| stem.to_str())
+        .map(str::trim)
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("Imported image")
+        .to_string();
+
+    if let Some(metadata) = extract_metadata(&bytes)? {
+        let reference_images = find_references_by_hash(&recorded_references(&metadata)).await?;
+        return Ok(import_own_metadata(
+            metadata,
+            reference_images,
+            template_name,
+        ));
+    }
+
+    if detect_image_format(&bytes)? == image::ImageFormat::Png {
+        let chunks = read_png_text_chunks(&bytes)?;
+        if let Some(generation) = parse_foreign_generation(&chunks) {
+            return Ok(import_foreign_generation(generation, template_name));
+        }
+    }
+
+    Err("No generation metadata found in image.".into())
+}
+
+fn import_own_metadata(
+    metadata: GenerationMetadata,
+    reference_images: Vec<ReferenceImagePayload>,
+    template_name: String,
+) -> ImportedGeneration {
+    let template = SavePromptsPayload {
+        id: None,
+        name: template_name,
+        system_prompt: metadata.system_prompt.clone().unwrap_or_default(),
+        user_prompt: metadata.prompt.clone(),
+        variables: Vec::new(),
+        sample_images: Vec::new(),
+        thumbnail: None,
+        tags: None,
+        folder: None,
+        favorite: None,
+        note: None,
+    };
+
+    ImportedGeneration {
+        source: metadata.generator,
+        request: GenerateImageRequest {
+            api_key: None,
+            model: Some(metadata.model),
+            system_prompt: metadata.system_prompt,
+            image_prompt: metadata.prompt,
+            reference_images,
+            size: metadata.parameters.size,
+            quality: metadata.parameters.quality,
+            style: metadata.parameters.style,
+            user: None,
+            seed: metadata.seed,
+            naming: None,
+            post_process: None,
+            parent_ids: Vec::new(),
+            template: None,
+        },
+        template,
+    }
+}
+
+fn import_foreign_generation(
+    generation: ForeignGeneration,
+    template_name: String,
+) -> ImportedGeneration {
+    // Other tools' checkpoints mean nothing to our providers, and they keep the
+    // negative prompt separate, so it is folded into the prompt text.
+    let image_prompt = match generation.negative_prompt {
+        Some(negative) => format!("{}\n\nAvoid: {}", generation.prompt, negative),
+        None => generation.prompt,
+    };
+
+    ImportedGeneration {
+        source: generation.source.to_string(),
+        request: GenerateImageRequest {
+            api_key: None,
+            model: None,
+            system_prompt: None,
+            image_prompt: image_prompt.clone(),
+            reference_images: Vec::new(),
+            size: generation.size,
+            quality: None,
+            style: None,
+            user: None,
+            seed: generation.seed.and_then(|seed| u32::try_from(seed).ok()),
+            naming: None,
+            post_process: None,
+            parent_ids: Vec::new(),
+            template: None,
+        },
+        template: SavePromptsPayload {
+            id: None,
+            name: template_name,
+            system_prompt: String::new(),
+            user_prompt: image_prompt,
+            variables: Vec::new(),
+            sample_images: Vec::new(),
+            thumbnail: None,
+            tags: None,
+            folder: None,
+            favorite: None,
+            note: None,
+        },
+    }
+}
+
+/// The recorded references as hash and slot pairs. Images saved before slots
+/// were recorded only have hashes.
+fn recorded_references(metadata: &GenerationMetadata) -> Vec<(String, Option<String>)> {
+    if metadata.reference_slots.is_empty() {
+        return metadata
+            .reference_hashes
+            .iter()
+            .map(|hash| (hash.clone(), None))
+            .collect();
+    }
+    metadata
+        .reference_slots
+        .iter()
+        .map(|reference| (reference.hash.clone(), Some(reference.slot.clone())))
+        .collect()
+}
+
+/// Looks up input images whose content hash matches a recorded reference and
+/// restores the slot each was sent under.
+async fn find_references_by_hash(
+    references: &[(String, Option<String>)],
+) -> Result<Vec<ReferenceImagePayload>, String> {
+    if references.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dir = ensure_input_dir().await?;
+    let mut entries = fs::read_dir(&dir)
+        .await
+        .map_err(|err| format!("Unable to read directory '{}': {}", dir.display(), err))?;
+
+    let mut found: Vec<(usize, ReferenceImagePayload)> = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|err| format!("Failed to iterate directory '{}': {}", dir.display(), err))?
+    {
+        let path = entry.path();
+        let mime_type = resolve_mime_type(None, &path);
+        if !mime_type.starts_with("image/") {
+            continue;
+        }
+
+        let Ok(bytes) = fs::read(&path).await else {
+            continue;
+        };
+        let hash = content_hash(&bytes);
+        // The same image may have been sent under several slots.
+        for (index, (_, slot)) in references
+            .iter()
+            .enumerate()
+            .filter(|(_, (candidate, _))| *candidate == hash)
+        {
+            found.push((
+                index,
+                ReferenceImagePayload {
+                    mime_type: Some(mime_type.clone()),
+                    data_base64: BASE64_ENGINE.encode(&bytes),
+                    slot: slot.clone(),
+                    file_name: entry.file_name().into_string().ok(),
+                },
+            ));
+        }
+    }
+
+    found.sort_by_key(|(index, _)| *index);
+    found.dedup_by_key(|(index, _)| *index);
+    Ok(found.into_iter().map(|(_, reference)| reference).collect())
+}
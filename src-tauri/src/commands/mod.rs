@@ -1,3 +1,18 @@
+pub mod background;
+pub mod captions;
+pub mod edit;
+pub mod enhance;
+pub mod export;
 pub mod generate;
 pub mod library;
+pub mod lineage;
+pub mod metadata;
+pub mod preflight;
+pub mod prompt_bundles;
+pub mod prompt_search;
+pub mod prompt_versions;
 pub mod prompts;
+pub mod reference;
+pub mod sessions;
+pub mod upscale;
+pub mod variations;
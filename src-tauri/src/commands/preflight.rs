@@ -0,0 +1,489 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::commands::generate::{
+    apply_default_system_prompt, build_generation_input, plan_output_name, reference_slot_name,
+};
+use crate::constants::{DEFAULT_IMAGE_MODEL, GEMINI_ASPECT_RATIOS, GEMINI_IMAGE_MODELS};
+use crate::image_utils::image_dimensions;
+use crate::llm::{require_api_key, resolve_model};
+use crate::models::{
+    GenerateImageRequest, PreflightCheck, PreflightIssue, PreflightReport, PreflightSeverity,
+};
+use crate::post_process::validate_post_process;
+
+/// Gemini rejects requests whose inline data exceeds this.
+const GEMINI_INLINE_REQUEST_LIMIT: u64 = 20 * 1024 * 1024;
+/// Image models follow instructions best with at most this many references.
+const MAX_RECOMMENDED_REFERENCES: usize = 3;
+/// Rough average for English prompt text.
+const CHARS_PER_TOKEN: u64 = 4;
+/// Gemini bills each image, or each 768px tile of a larger one, at this rate.
+const TOKENS_PER_IMAGE_TILE: u64 = 258;
+const IMAGE_TILE_SIZE: u32 = 768;
+const SMALL_IMAGE_SIZE: u32 = 384;
+
+/// Checks a generation request without sending it: estimates tokens and
+/// inline payload size and flags problems that would make the call fail or
+/// waste a generation. Builds the same input as `generate_image`, but never
+/// uploads references.
+#[tauri::command]
+pub async fn preflight_generation(
+    mut payload: GenerateImageRequest,
+) -> Result<PreflightReport, String> {
+    apply_default_system_prompt(&mut payload).await;
+    Ok(preflight_report(&payload))
+}
+
+/// Runs every check on a request whose default system prompt has already
+/// been applied.
+fn preflight_report(payload: &GenerateImageRequest) -> PreflightReport {
+    let mut issues = Vec::new();
+    let mut issue = |check, severity, message: String| {
+        issues.push(PreflightIssue {
+            check,
+            severity,
+            message,
+        })
+    };
+
+    if require_api_key(payload.api_key.as_deref()).is_err() {
+        issue(
+            PreflightCheck::MissingApiKey,
+            PreflightSeverity::Error,
+            "No API key is set.".into(),
+        );
+    }
+    if payload.image_prompt.trim().is_empty() {
+        issue(
+            PreflightCheck::EmptyPrompt,
+            PreflightSeverity::Error,
+            "The image prompt is empty.".into(),
+        );
+    }
+
+    let input = build_generation_input(payload);
+
+    if input.system_prompt.is_none() {
+        issue(
+            PreflightCheck::EmptySystemPrompt,
+            PreflightSeverity::Warning,
+            "There is no system prompt and no default system prompt is set.".into(),
+        );
+    }
+
+    for err in &input.invalid_references {
+        issue(
+            PreflightCheck::InvalidReference,
+            PreflightSeverity::Error,
+            err.clone(),
+        );
+    }
+
+    let slots: Vec<String> = payload
+        .reference_images
+        .iter()
+        .enumerate()
+        .filter(|(_, reference)| !reference.data_base64.trim().is_empty())
+        .map(|(index, reference)| reference_slot_name(reference, index))
+        .collect();
+    let mut seen_slots = HashSet::new();
+    for slot in &slots {
+        if !seen_slots.insert(slot.as_str()) {
+            issue(
+                PreflightCheck::DuplicateSlot,
+                PreflightSeverity::Warning,
+                format!("More than one reference uses the slot {{{}}}.", slot),
+            );
+        }
+    }
+
+    let mut first_by_hash: HashMap<&str, &str> = HashMap::new();
+    for reference in &input.references {
+        match first_by_hash.get(reference.original_hash.as_str()) {
+            Some(first) => issue(
+                PreflightCheck::DuplicateReference,
+                PreflightSeverity::Warning,
+                format!(
+                    "Reference {{{}}} is the same image as {{{}}}.",
+                    reference.slot_name, first
+                ),
+            ),
+            None => {
+                first_by_hash.insert(&reference.original_hash, &reference.slot_name);
+            }
+        }
+    }
+
+    if input.references.len() > MAX_RECOMMENDED_REFERENCES {
+        issue(
+            PreflightCheck::TooManyReferences,
+            PreflightSeverity::Warning,
+            format!(
+                "{} references are attached; image models work best with up to {}.",
+                input.references.len(),
+                MAX_RECOMMENDED_REFERENCES
+            ),
+        );
+    }
+
+    let texts = [
+        (
+            "system prompt",
+            input.system_prompt.as_deref().unwrap_or(""),
+        ),
+        ("image prompt", payload.image_prompt.as_str()),
+    ];
+    for (label, text) in texts {
+        let scan = scan_placeholders(text);
+        for placeholder in scan.placeholders {
+            if !slots.iter().any(|slot| slot == placeholder) {
+                issue(
+                    PreflightCheck::UnresolvedPlaceholder,
+                    PreflightSeverity::Warning,
+                    format!(
+                        "The {} mentions {{{}}}, but no reference fills that slot.",
+                        label, placeholder
+                    ),
+                );
+            }
+        }
+        if scan.template_syntax {
+            issue(
+                PreflightCheck::UnrenderedTemplate,
+                PreflightSeverity::Warning,
+                format!(
+                    "The {} still contains template syntax such as {{{{ }}}}; render the template first.",
+                    label
+                ),
+            );
+        }
+    }
+
+    let mut inline_image_bytes = 0;
+    let mut uploaded_image_bytes = 0;
+    let mut image_tokens = 0;
+    for reference in &input.references {
+        let size = reference.image.bytes.len() as u64;
+        if reference.needs_upload() {
+            uploaded_image_bytes += size;
+        } else {
+            inline_image_bytes += size.div_ceil(3) * 4;
+        }
+        image_tokens += image_dimensions(&reference.image.bytes)
+            .map(|(width, height)| image_token_estimate(width, height))
+            .unwrap_or(TOKENS_PER_IMAGE_TILE);
+    }
+
+    if inline_image_bytes > GEMINI_INLINE_REQUEST_LIMIT {
+        issue(
+            PreflightCheck::InlineSizeLimit,
+            PreflightSeverity::Error,
+            format!(
+                "Inline references total {:.1} MB, over the provider's {:.0} MB request limit.",
+                megabytes(inline_image_bytes),
+                megabytes(GEMINI_INLINE_REQUEST_LIMIT)
+            ),
+        );
+    }
+
+    // The same checks `generate_image` runs before calling the provider.
+    if let Some(Err(err)) = payload.post_process.as_ref().map(validate_post_process) {
+        issue(
+            PreflightCheck::InvalidPostProcess,
+            PreflightSeverity::Error,
+            format!("Invalid post-processing options: {}", err),
+        );
+    }
+
+    let model = resolve_model(payload.model.as_deref(), DEFAULT_IMAGE_MODEL);
+    if let Err(err) = plan_output_name(
+        payload.naming.as_ref(),
+        payload.image_prompt.trim(),
+        &model,
+        payload.seed,
+        1,
+    ) {
+        issue(
+            PreflightCheck::InvalidOutputName,
+            PreflightSeverity::Error,
+            err,
+        );
+    }
+
+    check_model(&model, payload, &mut issue);
+
+    let text_tokens = [input.system_prompt.as_deref(), Some(&input.user_prompt)]
+        .into_iter()
+        .flatten()
+        .map(|text| (text.chars().count() as u64).div_ceil(CHARS_PER_TOKEN))
+        .sum();
+
+    let ready = !issues
+        .iter()
+        .any(|issue| issue.severity == PreflightSeverity::Error);
+    PreflightReport {
+        model,
+        estimated_tokens: text_tokens + image_tokens,
+        text_tokens,
+        image_tokens,
+        inline_image_bytes,
+        inline_limit_bytes: GEMINI_INLINE_REQUEST_LIMIT,
+        uploaded_image_bytes,
+        issues,
+        ready,
+    }
+}
+
+/// Flags models outside the known image models and parameters Gemini only
+/// receives as prompt text.
+fn check_model(
+    model: &str,
+    payload: &GenerateImageRequest,
+    issue: &mut impl FnMut(PreflightCheck, PreflightSeverity, String),
+) {
+    if !GEMINI_IMAGE_MODELS.contains(&model) {
+        issue(
+            PreflightCheck::UnsupportedModel,
+            PreflightSeverity::Warning,
+            format!(
+                "'{}' is not a known Gemini image model ({}); it may not return images.",
+                model,
+                GEMINI_IMAGE_MODELS.join(", ")
+            ),
+        );
+    }
+
+    let hints = [("quality", &payload.quality), ("style", &payload.style)];
+    for (name, value) in hints {
+        if value
+            .as_deref()
+            .is_some_and(|value| !value.trim().is_empty())
+        {
+            issue(
+                PreflightCheck::UnsupportedParameter,
+                PreflightSeverity::Info,
+                format!(
+                    "Gemini has no {} setting; it is added to the prompt as a hint.",
+                    name
+                ),
+            );
+        }
+    }
+
+    if let Some(size) = payload
+        .size
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        if !GEMINI_ASPECT_RATIOS.contains(&size) {
+            issue(
+                PreflightCheck::UnsupportedParameter,
+                PreflightSeverity::Warning,
+                format!(
+                    "Gemini picks its own resolution and only follows aspect ratios ({}); '{}' may be ignored.",
+                    GEMINI_ASPECT_RATIOS.join(", "),
+                    size
+                ),
+            );
+        }
+    }
+}
+
+struct PlaceholderScan<'a> {
+    /// Names inside single braces, such as `img_1` in `{img_1}`.
+    placeholders: Vec<&'a str>,
+    /// Whether `{{`, `{%` or `{#` appears, i.e. an unrendered template.
+    template_syntax: bool,
+}
+
+fn scan_placeholders(text: &str) -> PlaceholderScan<'_> {
+    let mut scan = PlaceholderScan {
+        placeholders: Vec::new(),
+        template_syntax: false,
+    };
+
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        if after.starts_with(['{', '%', '#']) {
+            scan.template_syntax = true;
+            rest = &after[1..];
+            continue;
+        }
+
+        let name_len = after
+            .find(|character: char| !(character.is_ascii_alphanumeric() || character == '_'))
+            .unwrap_or(after.len());
+        if name_len > 0 && after[name_len..].starts_with('}') {
+            let name = &after[..name_len];
+            if !scan.placeholders.contains(&name) {
+                scan.placeholders.push(name);
+            }
+        }
+        rest = after;
+    }
+    scan
+}
+
+/// Gemini counts small images as one tile and splits larger ones into
+/// 768px tiles.
+fn image_token_estimate(width: u32, height: u32) -> u64 {
+    if width <= SMALL_IMAGE_SIZE && height <= SMALL_IMAGE_SIZE {
+        return TOKENS_PER_IMAGE_TILE;
+    }
+    let tiles =
+        u64::from(width.div_ceil(IMAGE_TILE_SIZE)) * u64::from(height.div_ceil(IMAGE_TILE_SIZE));
+    tiles * TOKENS_PER_IMAGE_TILE
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+    use base64::Engine;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use serde_json::json;
+
+    use crate::image_utils::encode_image;
+
+    /// Base64 PNG of `size`x`size` pseudo-random pixels; noise keeps the PNG
+    /// close to its raw size. `seed` makes distinct images.
+    fn noise_png(size: u32, seed: u32) -> String {
+        let mut state = seed.wrapping_mul(2_654_435_761).wrapping_add(1);
+        let image = RgbImage::from_fn(size, size, |_, _| {
+            let mut channel = || {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            };
+            image::Rgb([channel(), channel(), channel()])
+        });
+        let bytes = encode_image(&DynamicImage::ImageRgb8(image), ImageFormat::Png).expect("png");
+        BASE64_ENGINE.encode(bytes)
+    }
+
+    fn request(prompt: &str, references: &[(Option<&str>, &str)]) -> GenerateImageRequest {
+        let references: Vec<_> = references
+            .iter()
+            .map(|(slot, data)| json!({ "slot": slot, "dataBase64": data }))
+            .collect();
+        serde_json::from_value(json!({
+            "apiKey": "key",
+            "systemPrompt": "You draw pictures.",
+            "imagePrompt": prompt,
+            "referenceImages": references,
+        }))
+        .expect("request")
+    }
+
+    fn issues_of(report: &PreflightReport, check: PreflightCheck) -> Vec<&PreflightIssue> {
+        report
+            .issues
+            .iter()
+            .filter(|issue| issue.check == check)
+            .collect()
+    }
+
+    #[test]
+    fn clean_request_is_ready() {
+        let image = noise_png(8, 1);
+        let report = preflight_report(&request("A cat like {img_1}", &[(Some("img_1"), &image)]));
+
+        assert!(report.ready);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!(report.model, DEFAULT_IMAGE_MODEL);
+        assert!(report.image_tokens > 0 && report.text_tokens > 0);
+    }
+
+    #[test]
+    fn unfilled_placeholders_and_template_syntax_are_flagged() {
+        let image = noise_png(8, 1);
+        let report = preflight_report(&request(
+            "Put {img_1} beside {img_2} in {{ place }}",
+            &[(Some("img_1"), &image)],
+        ));
+
+        let unresolved = issues_of(&report, PreflightCheck::UnresolvedPlaceholder);
+        assert_eq!(unresolved.len(), 1);
+        assert!(unresolved[0].message.contains("{img_2}"));
+        assert_eq!(
+            issues_of(&report, PreflightCheck::UnrenderedTemplate).len(),
+            1
+        );
+        assert!(report.ready);
+    }
+
+    #[test]
+    fn unnamed_references_fill_their_default_slots() {
+        let image = noise_png(8, 1);
+        let report = preflight_report(&request("Use {reference_0}", &[(None, &image)]));
+        assert!(issues_of(&report, PreflightCheck::UnresolvedPlaceholder).is_empty());
+    }
+
+    #[test]
+    fn duplicate_references_and_slots_are_warnings() {
+        let first = noise_png(8, 1);
+        let second = noise_png(8, 2);
+        let report = preflight_report(&request(
+            "Combine {a} and {b}",
+            &[
+                (Some("a"), &first),
+                (Some("b"), &first),
+                (Some("b"), &second),
+            ],
+        ));
+
+        let duplicates = issues_of(&report, PreflightCheck::DuplicateReference);
+        assert_eq!(duplicates.len(), 1);
+        assert!(duplicates[0]
+            .message
+            .contains("{b} is the same image as {a}"));
+        let slots = issues_of(&report, PreflightCheck::DuplicateSlot);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].severity, PreflightSeverity::Warning);
+        assert!(report.ready);
+    }
+
+    #[test]
+    fn invalid_reference_blocks_the_request() {
+        let report = preflight_report(&request("A cat", &[(Some("a"), "bm90IGFuIGltYWdl")]));
+        assert_eq!(
+            issues_of(&report, PreflightCheck::InvalidReference).len(),
+            1
+        );
+        assert!(!report.ready);
+    }
+
+    #[test]
+    fn inline_references_over_the_request_limit_are_an_error() {
+        // Each PNG stays just under the Files API threshold, so all are inline.
+        let images: Vec<String> = (0..16).map(|seed| noise_png(580, seed)).collect();
+        let references: Vec<_> = images.iter().map(|data| (None, data.as_str())).collect();
+        let report = preflight_report(&request("A collage", &references));
+
+        assert_eq!(report.uploaded_image_bytes, 0);
+        assert!(report.inline_image_bytes > GEMINI_INLINE_REQUEST_LIMIT);
+        let limit = issues_of(&report, PreflightCheck::InlineSizeLimit);
+        assert_eq!(limit.len(), 1);
+        assert_eq!(limit[0].severity, PreflightSeverity::Error);
+        assert!(!report.ready);
+    }
+
+    #[test]
+    fn unknown_models_are_a_warning() {
+        let mut payload = request("A cat", &[]);
+        payload.model = Some("gemini-2.5-pro".to_string());
+        let report = preflight_report(&payload);
+
+        let model = issues_of(&report, PreflightCheck::UnsupportedModel);
+        assert_eq!(model.len(), 1);
+        assert_eq!(model[0].severity, PreflightSeverity::Warning);
+        assert!(report.ready);
+    }
+}
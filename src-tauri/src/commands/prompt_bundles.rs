@@ -0,0 +1,591 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::fs;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::commands::prompt_versions::{open_prompt_versions, record_prompt_version};
+use crate::commands::prompts::{
+    generate_unique_id, open_prompt_templates, read_prompt_templates, PromptTemplates,
+};
+use crate::constants::DEFAULT_MAX_UPLOAD_DIMENSION;
+use crate::fs_utils::{
+    ensure_input_dir, ensure_output_dir, ensure_unique_file_name, resolve_library_path,
+    sanitize_file_name,
+};
+use crate::image_utils::normalize_image;
+use crate::models::{
+    ExportPromptTemplatesPayload, ExportPromptTemplatesResult, ImportConflictStrategy,
+    ImportPromptTemplatesPayload, ImportPromptTemplatesReport, PromptBundleFormat,
+    TemplateImportAction, TemplateImportChange,
+};
+use crate::templates::validate_template;
+
+const PROMPT_BUNDLE_FORMAT: &str = "image-gen/prompt-bundle";
+const PROMPT_BUNDLE_VERSION: u32 = 1;
+const BUNDLE_MANIFEST_NAME: &str = "bundle.json";
+const DEFAULT_BUNDLE_NAME: &str = "prompt-templates";
+/// Limits on what an import reads into memory, so a corrupt or crafted
+/// bundle is refused instead of exhausting it.
+const MAX_BUNDLE_BYTES: u64 = 512 * 1024 * 1024;
+const MAX_MANIFEST_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_ASSET_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Portable set of templates. Templates refer to their sample images and
+/// thumbnail by asset path instead of library id.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptBundle {
+    format: String,
+    version: u32,
+    exported_at: u64,
+    templates: Vec<PromptTemplates>,
+    #[serde(default)]
+    assets: Vec<BundleAsset>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleAsset {
+    path: String,
+    /// Inline image for JSON bundles; zip bundles store the file at `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data_base64: Option<String>,
+}
+
+/// Writes the selected templates and the images they reference to a JSON or
+/// zip bundle in `destination_dir`.
+#[tauri::command]
+pub async fn export_prompt_templates(
+    payload: ExportPromptTemplatesPayload,
+) -> Result<ExportPromptTemplatesResult, String> {
+    if payload.ids.is_empty() {
+        return Err("No prompt templates selected for export.".into());
+    }
+
+    let stored = read_prompt_templates().await?;
+    let mut templates = Vec::with_capacity(payload.ids.len());
+    for id in &payload.ids {
+        let template = stored
+            .iter()
+            .find(|template| &template.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Prompt template with id '{}' not found.", id))?;
+        templates.push(template);
+    }
+
+    let mut packer = AssetPacker::default();
+    for template in &mut templates {
+        let mut sample_images = Vec::new();
+        for reference in &template.sample_images {
+            if let Some(path) = packer.pack(reference).await {
+                sample_images.push(path);
+            }
+        }
+        template.sample_images = sample_images;
+        template.thumbnail = match template.thumbnail.take() {
+            Some(reference) => packer.pack(&reference).await,
+            None => None,
+        };
+    }
+
+    let destination = PathBuf::from(payload.destination_dir.trim());
+    if destination.as_os_str().is_empty() {
+        return Err("Export destination cannot be empty.".into());
+    }
+    fs::create_dir_all(&destination).await.map_err(|err| {
+        format!(
+            "Unable to create export directory '{}': {}",
+            destination.display(),
+            err
+        )
+    })?;
+
+    let extension = match payload.format {
+        PromptBundleFormat::Json => "json",
+        PromptBundleFormat::Zip => "zip",
+    };
+    let stem = payload
+        .file_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_BUNDLE_NAME);
+    let file_name =
+        sanitize_file_name(stem).ok_or_else(|| format!("Invalid file name supplied: {}", stem))?;
+    let file_name = Path::new(&file_name)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned();
+    let file_name = ensure_unique_file_name(&destination, &file_name).await?;
+
+    let template_count = templates.len();
+    let asset_count = packer.files.len();
+    let mut bundle = PromptBundle {
+        format: PROMPT_BUNDLE_FORMAT.to_string(),
+        version: PROMPT_BUNDLE_VERSION,
+        exported_at: now(),
+        templates,
+        assets: Vec::with_capacity(asset_count),
+    };
+
+    let contents = match payload.format {
+        PromptBundleFormat::Json => {
+            for (path, bytes) in &packer.files {
+                bundle.assets.push(BundleAsset {
+                    path: path.clone(),
+                    data_base64: Some(BASE64_ENGINE.encode(bytes)),
+                });
+            }
+            serde_json::to_vec_pretty(&bundle)
+                .map_err(|err| format!("Unable to serialise prompt bundle: {}", err))?
+        }
+        PromptBundleFormat::Zip => {
+            bundle.assets = packer
+                .files
+                .iter()
+                .map(|(path, _)| BundleAsset {
+                    path: path.clone(),
+                    data_base64: None,
+                })
+                .collect();
+            write_bundle_archive(&bundle, &packer.files)?
+        }
+    };
+
+    let path = destination.join(&file_name);
+    fs::write(&path, contents)
+        .await
+        .map_err(|err| format!("Unable to write '{}': {}", path.display(), err))?;
+
+    Ok(ExportPromptTemplatesResult {
+        path: path.to_string_lossy().into_owned(),
+        templates: template_count,
+        assets: asset_count,
+    })
+}
+
+/// Imports a bundle written by `export_prompt_templates`. Templates whose id
+/// or name is already in use are skipped, renamed or overwritten according to
+/// `conflict`; referenced images are copied into `input/`.
+#[tauri::command]
+pub async fn import_prompt_templates(
+    payload: ImportPromptTemplatesPayload,
+) -> Result<ImportPromptTemplatesReport, String> {
+    let path = PathBuf::from(payload.path.trim());
+    let size = fs::metadata(&path)
+        .await
+        .map_err(|err| format!("Unable to read '{}': {}", path.display(), err))?
+        .len();
+    if size > MAX_BUNDLE_BYTES {
+        return Err(format!(
+            "Prompt bundle is too large ({} MB, at most {} MB).",
+            size / (1024 * 1024),
+            MAX_BUNDLE_BYTES / (1024 * 1024)
+        ));
+    }
+    let contents = fs::read(&path)
+        .await
+        .map_err(|err| format!("Unable to read '{}': {}", path.display(), err))?;
+    let (bundle, files) = parse_bundle(&contents)?;
+
+    for template in &bundle.templates {
+        if template.name.trim().is_empty() {
+            return Err("Invalid prompt bundle: a template has no name.".into());
+        }
+        validate_template(
+            &[&template.system_prompt, &template.user_prompt],
+            &template.variables,
+        )
+        .map_err(|err| format!("Invalid template '{}' in bundle: {}", template.name, err))?;
+    }
+
+    let source_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("bundle")
+        .to_string();
+    let note = Some(format!("Imported from {}", source_name));
+
+    let mut templates = open_prompt_templates().await?;
+    let mut versions = open_prompt_versions().await?;
+    let mut unpacker = AssetUnpacker {
+        files,
+        imported: HashMap::new(),
+        warnings: Vec::new(),
+    };
+    // Assets are written as templates are processed, so remove them again if
+    // the import fails before the templates are saved.
+    let imported = async {
+        let mut changes = Vec::with_capacity(bundle.templates.len());
+
+        for mut incoming in bundle.templates {
+            let bundle_id = incoming.id.clone();
+            let bundle_name = incoming.name.clone();
+            let conflict = templates
+                .iter()
+                .position(|template| template.id == incoming.id)
+                .or_else(|| {
+                    templates
+                        .iter()
+                        .position(|template| template.name == incoming.name)
+                });
+
+            let action = match (conflict, payload.conflict) {
+                (None, _) => TemplateImportAction::Created,
+                (Some(index), ImportConflictStrategy::Skip) => {
+                    changes.push(TemplateImportChange {
+                        id: templates[index].id.clone(),
+                        name: templates[index].name.clone(),
+                        bundle_id,
+                        bundle_name,
+                        action: TemplateImportAction::Skipped,
+                    });
+                    continue;
+                }
+                (Some(_), ImportConflictStrategy::Rename) => TemplateImportAction::Renamed,
+                (Some(_), ImportConflictStrategy::Overwrite) => TemplateImportAction::Overwritten,
+            };
+
+            let mut sample_images = Vec::new();
+            for asset in &incoming.sample_images {
+                if let Some(reference) = unpacker.unpack(asset, &bundle_name).await? {
+                    sample_images.push(reference);
+                }
+            }
+            incoming.sample_images = sample_images;
+            incoming.thumbnail = match incoming.thumbnail.take() {
+                Some(asset) => unpacker.unpack(&asset, &bundle_name).await?,
+                None => None,
+            };
+
+            let imported = match (action, conflict) {
+                (TemplateImportAction::Overwritten, Some(index)) => {
+                    // The id may match one template while the name belongs to
+                    // another; keep names unique.
+                    if templates.iter().enumerate().any(|(other, template)| {
+                        other != index && template.name == incoming.name
+                    }) {
+                        let name = unique_template_name(&templates, &incoming.name);
+                        unpacker.warnings.push(format!(
+                            "Template '{}' was imported as '{}' because another template already uses that name.",
+                            incoming.name, name
+                        ));
+                        incoming.name = name;
+                    }
+                    let existing = &mut templates[index];
+                    let previous = existing.clone();
+                    existing.name = incoming.name;
+                    existing.system_prompt = incoming.system_prompt;
+                    existing.user_prompt = incoming.user_prompt;
+                    existing.variables = incoming.variables;
+                    existing.sample_images = incoming.sample_images;
+                    existing.thumbnail = incoming.thumbnail;
+                    existing.tags = incoming.tags;
+                    existing.folder = incoming.folder;
+                    record_prompt_version(&mut versions, existing, Some(&previous), note.clone());
+                    existing.clone()
+                }
+                _ => {
+                    if incoming.id.trim().is_empty()
+                        || templates.iter().any(|template| template.id == incoming.id)
+                    {
+                        incoming.id = generate_unique_id(&templates);
+                    }
+                    // An id clash alone only needs the new id above; the
+                    // name changes only when it is taken too.
+                    if matches!(action, TemplateImportAction::Renamed)
+                        && templates
+                            .iter()
+                            .any(|template| template.name == incoming.name)
+                    {
+                        incoming.name = unique_template_name(&templates, &incoming.name);
+                    }
+                    incoming.date_created = now();
+                    incoming.version = 0;
+                    incoming.usage_count = 0;
+                    record_prompt_version(&mut versions, &mut incoming, None, note.clone());
+                    templates.push(incoming.clone());
+                    incoming
+                }
+            };
+
+            changes.push(TemplateImportChange {
+                id: imported.id,
+                name: imported.name,
+                bundle_id,
+                bundle_name,
+                action,
+            });
+        }
+
+        templates.save().await?;
+        Ok::<_, String>(changes)
+    }
+    .await;
+    let changes = match imported {
+        Ok(changes) => changes,
+        Err(err) => {
+            unpacker.discard().await;
+            return Err(err);
+        }
+    };
+    // The templates and assets are already in place, so a lost history entry
+    // is not worth failing the import over.
+    if let Err(err) = versions.save().await {
+        unpacker.warnings.push(format!(
+            "Templates were imported, but their version history could not be saved: {}",
+            err
+        ));
+    }
+
+    let mut assets: Vec<String> = unpacker.imported.into_values().collect();
+    assets.sort();
+    Ok(ImportPromptTemplatesReport {
+        changes,
+        assets,
+        warnings: unpacker.warnings,
+    })
+}
+
+/// Collects library images for export under unique `assets/` paths, packing
+/// each image once even when several templates share it.
+#[derive(Default)]
+struct AssetPacker {
+    packed: HashMap<String, String>,
+    used_names: HashSet<String>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl AssetPacker {
+    /// Returns the asset path for `reference`, or `None` when the image no
+    /// longer exists.
+    async fn pack(&mut self, reference: &str) -> Option<String> {
+        if let Some(path) = self.packed.get(reference) {
+            return Some(path.clone());
+        }
+
+        let (dir, id) = match reference.split_once('/') {
+            Some(("output", id)) => (ensure_output_dir().await.ok()?, id),
+            Some(("input", id)) => (ensure_input_dir().await.ok()?, id),
+            _ => (ensure_input_dir().await.ok()?, reference),
+        };
+        let source = resolve_library_path(&dir, id)?;
+        let bytes = fs::read(&source).await.ok()?;
+        let file_name = source.file_name()?.to_str()?.to_string();
+
+        let mut candidate = file_name.clone();
+        let mut counter = 1;
+        while !self.used_names.insert(candidate.clone()) {
+            candidate = format!("{counter}_{file_name}");
+            counter += 1;
+        }
+
+        let path = format!("assets/{candidate}");
+        self.packed.insert(reference.to_string(), path.clone());
+        self.files.push((path.clone(), bytes));
+        Some(path)
+    }
+}
+
+struct AssetUnpacker {
+    files: HashMap<String, Vec<u8>>,
+    imported: HashMap<String, String>,
+    warnings: Vec<String>,
+}
+
+impl AssetUnpacker {
+    /// Copies a bundled image into `input/` once and returns its
+    /// `input/<id>` reference. Assets go through the same normalisation as
+    /// uploads and are saved under their image extension, so a bundle cannot
+    /// place other files in the library. Missing assets become warnings.
+    async fn unpack(&mut self, asset: &str, template: &str) -> Result<Option<String>, String> {
+        if let Some(reference) = self.imported.get(asset) {
+            return Ok(Some(reference.clone()));
+        }
+        let Some(bytes) = self.files.get(asset) else {
+            self.warnings.push(format!(
+                "Template '{}' references missing asset '{}'.",
+                template, asset
+            ));
+            return Ok(None);
+        };
+
+        let name = Path::new(asset)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(sanitize_file_name)
+            .ok_or_else(|| format!("Invalid asset path in bundle: {}", asset))?;
+        let normalized = normalize_image(bytes, DEFAULT_MAX_UPLOAD_DIMENSION, None)
+            .map_err(|err| format!("Rejected asset '{}' in bundle: {}", asset, err))?;
+        let name = Path::new(&name)
+            .with_extension(normalized.extension())
+            .to_string_lossy()
+            .into_owned();
+
+        let input_dir = ensure_input_dir().await?;
+        let unique_name = ensure_unique_file_name(&input_dir, &name).await?;
+        let target = input_dir.join(&unique_name);
+        fs::write(&target, &normalized.bytes)
+            .await
+            .map_err(|err| format!("Unable to write file '{}': {}", unique_name, err))?;
+
+        let reference = format!("input/{unique_name}");
+        self.imported.insert(asset.to_string(), reference.clone());
+        Ok(Some(reference))
+    }
+
+    /// Deletes every asset written so far. Best effort: failures are logged
+    /// since the import is already being reported as failed.
+    async fn discard(&self) {
+        let Ok(input_dir) = ensure_input_dir().await else {
+            return;
+        };
+        for reference in self.imported.values() {
+            let name = reference.trim_start_matches("input/");
+            if let Err(err) = fs::remove_file(input_dir.join(name)).await {
+                eprintln!("Unable to remove imported asset '{}': {}", name, err);
+            }
+        }
+    }
+}
+
+fn write_bundle_archive(
+    bundle: &PromptBundle,
+    files: &[(String, Vec<u8>)],
+) -> Result<Vec<u8>, String> {
+    let manifest = serde_json::to_vec_pretty(bundle)
+        .map_err(|err| format!("Unable to serialise prompt bundle: {}", err))?;
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let manifest_options =
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Already-compressed image data gains nothing from deflate.
+    let asset_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    writer
+        .start_file(BUNDLE_MANIFEST_NAME, manifest_options)
+        .and_then(|_| writer.write_all(&manifest).map_err(Into::into))
+        .map_err(|err| format!("Unable to add bundle manifest to archive: {}", err))?;
+    for (path, bytes) in files {
+        writer
+            .start_file(path.as_str(), asset_options)
+            .and_then(|_| writer.write_all(bytes).map_err(Into::into))
+            .map_err(|err| format!("Unable to add '{}' to archive: {}", path, err))?;
+    }
+
+    Ok(writer
+        .finish()
+        .map_err(|err| format!("Unable to finish archive: {}", err))?
+        .into_inner())
+}
+
+/// Reads a JSON or zip bundle, checking its format marker and version before
+/// the full schema, and returns the manifest with asset bytes by path.
+fn parse_bundle(contents: &[u8]) -> Result<(PromptBundle, HashMap<String, Vec<u8>>), String> {
+    let mut archive = if contents.starts_with(b"PK") {
+        Some(
+            ZipArchive::new(Cursor::new(contents))
+                .map_err(|err| format!("Unable to open bundle archive: {}", err))?,
+        )
+    } else {
+        None
+    };
+
+    let manifest = match archive.as_mut() {
+        Some(archive) => read_archive_entry(archive, BUNDLE_MANIFEST_NAME, MAX_MANIFEST_BYTES)?,
+        None => contents.to_vec(),
+    };
+    let manifest: Value = serde_json::from_slice(&manifest)
+        .map_err(|err| format!("Invalid prompt bundle: {}", err))?;
+
+    if manifest.get("format").and_then(Value::as_str) != Some(PROMPT_BUNDLE_FORMAT) {
+        return Err("File is not a prompt template bundle.".into());
+    }
+    let version = manifest
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| "Invalid prompt bundle: missing version.".to_string())?;
+    if version == 0 || version > u64::from(PROMPT_BUNDLE_VERSION) {
+        return Err(format!(
+            "Unsupported prompt bundle version {}; this build reads up to version {}.",
+            version, PROMPT_BUNDLE_VERSION
+        ));
+    }
+
+    let bundle: PromptBundle = serde_json::from_value(manifest)
+        .map_err(|err| format!("Invalid prompt bundle: {}", err))?;
+
+    let mut files = HashMap::with_capacity(bundle.assets.len());
+    let mut total = 0;
+    for asset in &bundle.assets {
+        let bytes = match (&asset.data_base64, archive.as_mut()) {
+            (Some(data), _) => {
+                if data.len() as u64 / 4 * 3 > MAX_ASSET_BYTES {
+                    return Err(entry_too_large(&asset.path));
+                }
+                BASE64_ENGINE
+                    .decode(data.trim())
+                    .map_err(|err| format!("Invalid data for asset '{}': {}", asset.path, err))?
+            }
+            (None, Some(archive)) => read_archive_entry(archive, &asset.path, MAX_ASSET_BYTES)?,
+            (None, None) => continue,
+        };
+        total += bytes.len() as u64;
+        if total > MAX_BUNDLE_BYTES {
+            return Err("Prompt bundle assets are too large in total.".into());
+        }
+        files.insert(asset.path.clone(), bytes);
+    }
+
+    Ok((bundle, files))
+}
+
+/// Reads one archive entry, refusing entries larger than `limit` bytes by
+/// their declared size or, should that be wrong, by what they decompress to.
+fn read_archive_entry(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|err| format!("Bundle archive is missing '{}': {}", name, err))?;
+    if entry.size() > limit {
+        return Err(entry_too_large(name));
+    }
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|err| format!("Unable to read '{}' from archive: {}", name, err))?;
+    if bytes.len() as u64 > limit {
+        return Err(entry_too_large(name));
+    }
+    Ok(bytes)
+}
+
+fn entry_too_large(name: &str) -> String {
+    format!("'{}' in the prompt bundle is too large.", name)
+}
+
+fn unique_template_name(templates: &[PromptTemplates], name: &str) -> String {
+    (2..)
+        .map(|counter| format!("{} ({})", name, counter))
+        .find(|candidate| templates.iter().all(|template| &template.name != candidate))
+        .expect("an unused name always exists")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
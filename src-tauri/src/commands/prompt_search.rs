@@ -0,0 +1,131 @@
+use crate::commands::prompts::{read_prompt_templates, PromptTemplates};
+use crate::models::PromptSearchQuery;
+use serde::Serialize;
+
+/// Names scoring below this against the query are not treated as matches.
+const NAME_MATCH_THRESHOLD: f64 = 0.8;
+/// Score for a template whose prompts, tags or folder contain every term but
+/// whose name does not match.
+const TEXT_MATCH_SCORE: f64 = 0.6;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptSearchResult {
+    pub template: PromptTemplates,
+    pub score: f64,
+    pub name_match: bool,
+    pub text_match: bool,
+}
+
+/// Finds templates by fuzzy name, full text over their prompts, tags and
+/// folder. Tag, folder and favorite filters narrow the results first. With no
+/// text every remaining template matches, ordered favorites first and then
+/// by usage.
+#[tauri::command]
+pub async fn search_prompt_templates(
+    query: PromptSearchQuery,
+) -> Result<Vec<PromptSearchResult>, String> {
+    let text = query.text.as_deref().unwrap_or("").trim().to_lowercase();
+    let terms: Vec<&str> = text.split_whitespace().collect();
+    let folder = query
+        .folder
+        .as_deref()
+        .map(|folder| folder.trim().trim_matches('/'))
+        .filter(|folder| !folder.is_empty());
+
+    let mut results: Vec<PromptSearchResult> = read_prompt_templates()
+        .await?
+        .into_iter()
+        .filter(|template| !query.favorites_only || template.favorite)
+        .filter(|template| {
+            query.tags.iter().all(|tag| {
+                template
+                    .tags
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(tag.trim()))
+            })
+        })
+        .filter(|template| folder.is_none_or(|folder| in_folder(template, folder)))
+        .filter_map(|template| {
+            if terms.is_empty() {
+                return Some(PromptSearchResult {
+                    template,
+                    score: 0.0,
+                    name_match: false,
+                    text_match: false,
+                });
+            }
+
+            let name_score = name_score(&template.name, &text);
+            let name_match = name_score >= NAME_MATCH_THRESHOLD;
+            let text_match = contains_all_terms(&template, &terms);
+            if !name_match && !text_match {
+                return None;
+            }
+
+            let score = if name_match {
+                name_score
+            } else {
+                TEXT_MATCH_SCORE
+            };
+            Some(PromptSearchResult {
+                template,
+                score,
+                name_match,
+                text_match,
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.template.favorite.cmp(&a.template.favorite))
+            .then(b.template.usage_count.cmp(&a.template.usage_count))
+            .then_with(|| a.template.name.cmp(&b.template.name))
+    });
+    Ok(results)
+}
+
+/// Best of a whole-name comparison, a per-word comparison (so "hero" finds
+/// "Clean product hero shots") and an exact substring hit.
+fn name_score(name: &str, query: &str) -> f64 {
+    let name = name.to_lowercase();
+    if name.contains(query) {
+        return 1.0;
+    }
+
+    let whole = strsim::jaro_winkler(&name, query);
+    let words = query
+        .split_whitespace()
+        .map(|term| {
+            name.split(|character: char| !character.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(|word| strsim::jaro_winkler(word, term))
+                .fold(0.0, f64::max)
+        })
+        .fold(f64::INFINITY, f64::min);
+
+    whole.max(if words.is_finite() { words } else { 0.0 })
+}
+
+fn contains_all_terms(template: &PromptTemplates, terms: &[&str]) -> bool {
+    let haystack = format!(
+        "{}\n{}\n{}\n{}",
+        template.system_prompt,
+        template.user_prompt,
+        template.tags.join(" "),
+        template.folder.as_deref().unwrap_or("")
+    )
+    .to_lowercase();
+    terms.iter().all(|term| haystack.contains(term))
+}
+
+fn in_folder(template: &PromptTemplates, folder: &str) -> bool {
+    template.folder.as_deref().is_some_and(|candidate| {
+        candidate == folder
+            || candidate
+                .strip_prefix(folder)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
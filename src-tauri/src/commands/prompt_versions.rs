@@ -0,0 +1,261 @@
+use crate::commands::prompts::{open_prompt_templates, read_prompt_templates, PromptTemplates};
+use crate::fs_utils::ensure_input_dir;
+use crate::models::{PromptTemplateRef, TemplateVariable};
+use crate::store::{self, Document};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PROMPT_VERSIONS_FILE: &str = "prompt_versions.json";
+
+/// An immutable snapshot of a template, written on every save.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateVersion {
+    pub template_id: String,
+    pub version: u32,
+    pub name: String,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffTag {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptVersionDiff {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub from_name: String,
+    pub to_name: String,
+    pub system_prompt: Vec<DiffLine>,
+    pub user_prompt: Vec<DiffLine>,
+}
+
+/// Lists a template's versions, newest first.
+#[tauri::command]
+pub async fn list_prompt_versions(
+    template_id: String,
+) -> Result<Vec<PromptTemplateVersion>, String> {
+    let mut versions: Vec<PromptTemplateVersion> = read_prompt_versions()
+        .await?
+        .into_iter()
+        .filter(|version| version.template_id == template_id)
+        .collect();
+    versions.sort_by_key(|version| Reverse(version.version));
+    Ok(versions)
+}
+
+#[tauri::command]
+pub async fn diff_prompt_versions(
+    template_id: String,
+    from_version: u32,
+    to_version: u32,
+) -> Result<PromptVersionDiff, String> {
+    let versions = read_prompt_versions().await?;
+    let from = find_version(&versions, &template_id, from_version)?;
+    let to = find_version(&versions, &template_id, to_version)?;
+
+    Ok(PromptVersionDiff {
+        from_version,
+        to_version,
+        from_name: from.name.clone(),
+        to_name: to.name.clone(),
+        system_prompt: diff_lines(&from.system_prompt, &to.system_prompt),
+        user_prompt: diff_lines(&from.user_prompt, &to.user_prompt),
+    })
+}
+
+/// Makes an earlier version current again. History is append-only, so this
+/// records a new version with the old content rather than dropping the newer
+/// ones.
+#[tauri::command]
+pub async fn restore_prompt_version(
+    template_id: String,
+    version: u32,
+    note: Option<String>,
+) -> Result<PromptTemplates, String> {
+    let versions = read_prompt_versions().await?;
+    let snapshot = find_version(&versions, &template_id, version)?.clone();
+
+    let mut templates = open_prompt_templates().await?;
+    let mut versions = open_prompt_versions().await?;
+    let existing = templates
+        .iter_mut()
+        .find(|template| template.id == template_id)
+        .ok_or_else(|| format!("Prompt template with id '{}' not found.", template_id))?;
+
+    let previous = existing.clone();
+    existing.name = snapshot.name;
+    existing.system_prompt = snapshot.system_prompt;
+    existing.user_prompt = snapshot.user_prompt;
+    existing.variables = snapshot.variables;
+    let note = note.or_else(|| Some(format!("Restored version {}", version)));
+    record_prompt_version(&mut versions, existing, Some(&previous), note);
+    let restored = existing.clone();
+
+    templates.save().await?;
+    versions.save().await?;
+    Ok(restored)
+}
+
+/// Appends a version for `template` to `versions` and bumps its `version`.
+/// Templates saved before versioning existed get their previous content
+/// recorded first so it is not lost. Nothing is written here; callers save the
+/// templates before the history, so a failed template save never leaves a
+/// version behind for content that was not stored.
+pub(crate) fn record_prompt_version(
+    versions: &mut Vec<PromptTemplateVersion>,
+    template: &mut PromptTemplates,
+    previous: Option<&PromptTemplates>,
+    note: Option<String>,
+) {
+    let mut latest = versions
+        .iter()
+        .filter(|version| version.template_id == template.id)
+        .map(|version| version.version)
+        .max()
+        .unwrap_or(0);
+
+    if latest == 0 {
+        if let Some(previous) = previous {
+            latest = 1;
+            versions.push(snapshot(previous, latest, previous.date_created, None));
+        }
+    }
+
+    template.version = latest + 1;
+    versions.push(snapshot(
+        template,
+        template.version,
+        now(),
+        normalize_note(note),
+    ));
+}
+
+pub(crate) async fn remove_prompt_versions(template_id: &str) -> Result<(), String> {
+    let mut versions = open_prompt_versions().await?;
+    versions.retain(|version| version.template_id != template_id);
+    versions.save().await
+}
+
+/// Fills in the current version of a template referenced without one, so the
+/// history always records the exact wording used.
+pub(crate) async fn resolve_template_ref(
+    reference: Option<&PromptTemplateRef>,
+) -> Option<PromptTemplateRef> {
+    let reference = reference?;
+    if reference.version.is_some() {
+        return Some(reference.clone());
+    }
+
+    let current = read_prompt_templates()
+        .await
+        .ok()
+        .and_then(|templates| {
+            templates
+                .into_iter()
+                .find(|template| template.id == reference.id)
+        })
+        .map(|template| template.version)
+        .filter(|version| *version > 0);
+
+    Some(PromptTemplateRef {
+        id: reference.id.clone(),
+        version: current,
+    })
+}
+
+fn snapshot(
+    template: &PromptTemplates,
+    version: u32,
+    created_at: u64,
+    note: Option<String>,
+) -> PromptTemplateVersion {
+    PromptTemplateVersion {
+        template_id: template.id.clone(),
+        version,
+        name: template.name.clone(),
+        system_prompt: template.system_prompt.clone(),
+        user_prompt: template.user_prompt.clone(),
+        variables: template.variables.clone(),
+        created_at,
+        note,
+    }
+}
+
+fn find_version<'a>(
+    versions: &'a [PromptTemplateVersion],
+    template_id: &str,
+    version: u32,
+) -> Result<&'a PromptTemplateVersion, String> {
+    versions
+        .iter()
+        .find(|candidate| candidate.template_id == template_id && candidate.version == version)
+        .ok_or_else(|| {
+            format!(
+                "Version {} of prompt template '{}' not found.",
+                version, template_id
+            )
+        })
+}
+
+fn diff_lines(from: &str, to: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(from, to)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            tag: match change.tag() {
+                ChangeTag::Equal => DiffTag::Equal,
+                ChangeTag::Insert => DiffTag::Insert,
+                ChangeTag::Delete => DiffTag::Delete,
+            },
+            text: change.value().trim_end_matches(['\r', '\n']).to_string(),
+        })
+        .collect()
+}
+
+fn normalize_note(note: Option<String>) -> Option<String> {
+    note.map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+async fn prompt_versions_path() -> Result<PathBuf, String> {
+    let dir = ensure_input_dir().await?;
+    Ok(dir.join(PROMPT_VERSIONS_FILE))
+}
+
+async fn read_prompt_versions() -> Result<Vec<PromptTemplateVersion>, String> {
+    store::load(&prompt_versions_path().await?).await
+}
+
+/// Callers holding the prompts file must take it before this one, never after.
+pub(crate) async fn open_prompt_versions() -> Result<Document<Vec<PromptTemplateVersion>>, String> {
+    store::open(&prompt_versions_path().await?).await
+}
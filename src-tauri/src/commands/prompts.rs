@@ -1,10 +1,19 @@
-use crate::fs_utils::ensure_input_dir;
-use crate::models::SavePromptsPayload;
+use crate::commands::captions::caption_context;
+use crate::commands::prompt_versions::{
+    open_prompt_versions, record_prompt_version, remove_prompt_versions,
+};
+use crate::constants::{SYSTEM_PROMPT_FILE, USER_PROMPT_FILE};
+use crate::fs_utils::{ensure_input_dir, read_prompt_file, write_prompt_file};
+use crate::models::{
+    DefaultPrompts, RenderedPrompt, SavePromptsPayload, SetDefaultPromptsPayload, TemplateVariable,
+};
+use crate::store::{self, Document};
+use crate::templates::{render_template, resolve_variables, validate_template, CAPTIONS_VARIABLE};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::fs;
-use tokio::fs::try_exists;
 
 const PROMPT_TEMPLATES_FILE: &str = "prompts.json";
 
@@ -16,6 +25,25 @@ pub struct PromptTemplates {
     pub system_prompt: String,
     pub user_prompt: String,
     pub date_created: u64,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    /// Latest entry in the template's version history; 0 if never versioned.
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample_images: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// `/`-separated folder path, e.g. `ecommerce/heroes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    /// Number of generations that used this template.
+    #[serde(default)]
+    pub usage_count: u64,
 }
 
 impl PromptTemplates {
@@ -24,6 +52,7 @@ impl PromptTemplates {
         name: String,
         system_prompt: String,
         user_prompt: String,
+        variables: Vec<TemplateVariable>,
     ) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -36,6 +65,14 @@ impl PromptTemplates {
             system_prompt,
             user_prompt,
             date_created: now,
+            variables,
+            version: 0,
+            sample_images: Vec::new(),
+            thumbnail: None,
+            tags: Vec::new(),
+            folder: None,
+            favorite: false,
+            usage_count: 0,
         }
     }
 }
@@ -57,9 +94,19 @@ pub async fn save_prompts(payload: SavePromptsPayload) -> Result<PromptTemplates
         name,
         system_prompt,
         user_prompt,
+        variables,
+        sample_images,
+        thumbnail,
+        tags,
+        folder,
+        favorite,
+        note,
     } = payload;
 
-    let mut templates = read_prompt_templates().await?;
+    validate_template(&[&system_prompt, &user_prompt], &variables)?;
+
+    let mut templates = open_prompt_templates().await?;
+    let mut versions = open_prompt_versions().await?;
 
     if let Some(existing_id) = id.clone() {
         if let Some(index) = templates
@@ -70,13 +117,20 @@ pub async fn save_prompts(payload: SavePromptsPayload) -> Result<PromptTemplates
                 let existing = templates
                     .get_mut(index)
                     .expect("template index resolved from position must exist");
+                let previous = existing.clone();
                 existing.name = name.clone();
                 existing.system_prompt = system_prompt.clone();
                 existing.user_prompt = user_prompt.clone();
+                existing.variables = variables.clone();
+                existing.sample_images = sample_images.clone();
+                existing.thumbnail = thumbnail.clone();
+                organize_template(existing, tags.clone(), folder.clone(), favorite);
+                record_prompt_version(&mut versions, existing, Some(&previous), note.clone());
                 existing.clone()
             };
 
-            write_prompt_templates(&templates).await?;
+            templates.save().await?;
+            versions.save().await?;
             return Ok(updated);
         }
     }
@@ -87,30 +141,98 @@ pub async fn save_prompts(payload: SavePromptsPayload) -> Result<PromptTemplates
                 let existing = templates
                     .get_mut(index)
                     .expect("template index resolved from position must exist");
+                let previous = existing.clone();
                 existing.system_prompt = system_prompt.clone();
                 existing.user_prompt = user_prompt.clone();
+                existing.variables = variables.clone();
+                existing.sample_images = sample_images.clone();
+                existing.thumbnail = thumbnail.clone();
+                organize_template(existing, tags.clone(), folder.clone(), favorite);
+                record_prompt_version(&mut versions, existing, Some(&previous), note.clone());
                 existing.clone()
             };
 
-            write_prompt_templates(&templates).await?;
+            templates.save().await?;
+            versions.save().await?;
             return Ok(updated);
         }
     }
 
-    let mut template = PromptTemplates::new(id, name, system_prompt, user_prompt);
+    let mut template = PromptTemplates::new(id, name, system_prompt, user_prompt, variables);
+    template.sample_images = sample_images;
+    template.thumbnail = thumbnail;
+    organize_template(&mut template, tags, folder, favorite);
 
     if templates.iter().any(|existing| existing.id == template.id) {
         template.id = generate_unique_id(&templates);
     }
+    record_prompt_version(&mut versions, &mut template, None, note);
 
     templates.push(template.clone());
-    write_prompt_templates(&templates).await?;
+    templates.save().await?;
+    versions.save().await?;
     Ok(template)
 }
 
+/// Applies the organisational fields of a save; `None` keeps the current value.
+fn organize_template(
+    template: &mut PromptTemplates,
+    tags: Option<Vec<String>>,
+    folder: Option<String>,
+    favorite: Option<bool>,
+) {
+    if let Some(tags) = tags {
+        template.tags = normalize_tags(tags);
+    }
+    if let Some(folder) = folder {
+        template.folder = normalize_folder(&folder);
+    }
+    if let Some(favorite) = favorite {
+        template.favorite = favorite;
+    }
+}
+
+/// Trims tags and drops empty and case-insensitive duplicates.
+pub(crate) fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty()
+            && !normalized
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(tag))
+        {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+fn normalize_folder(folder: &str) -> Option<String> {
+    let segments: Vec<&str> = folder
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    (!segments.is_empty()).then(|| segments.join("/"))
+}
+
+/// Counts a generation against the template it was rendered from.
+pub(crate) async fn record_template_usage(template_id: &str) -> Result<(), String> {
+    let mut templates = open_prompt_templates().await?;
+    let Some(template) = templates
+        .iter_mut()
+        .find(|template| template.id == template_id)
+    else {
+        return Ok(());
+    };
+    template.usage_count += 1;
+    templates.save().await
+}
+
 #[tauri::command]
 pub async fn remove_prompts_by_id(id: String) -> Result<(), String> {
-    let mut templates = read_prompt_templates().await?;
+    let mut templates = open_prompt_templates().await?;
     let original_len = templates.len();
     templates.retain(|template| template.id != id);
 
@@ -118,60 +240,92 @@ pub async fn remove_prompts_by_id(id: String) -> Result<(), String> {
         return Err(format!("Prompt template with id '{}' not found.", id));
     }
 
-    write_prompt_templates(&templates).await?;
-    Ok(())
+    templates.save().await?;
+    remove_prompt_versions(&id).await
 }
 
+/// Renders a template's system and user prompts with `values`, validated
+/// against the template's declared variables. `images` binds reference slots
+/// to input image ids so their captions are available as
+/// `{{ captions.<slot>.short }}` and `{{ captions.<slot>.detailed }}`.
 #[tauri::command]
-pub async fn list_prompt_templates() -> Result<Vec<PromptTemplates>, String> {
-    read_prompt_templates().await
-}
+pub async fn render_prompt(
+    template_id: String,
+    values: Map<String, Value>,
+    images: Option<HashMap<String, String>>,
+) -> Result<RenderedPrompt, String> {
+    let templates = read_prompt_templates().await?;
+    let template = templates
+        .iter()
+        .find(|template| template.id == template_id)
+        .ok_or_else(|| format!("Prompt template with id '{}' not found.", template_id))?;
 
-async fn prompt_templates_path() -> Result<PathBuf, String> {
-    let dir = ensure_input_dir().await?;
-    Ok(dir.join(PROMPT_TEMPLATES_FILE))
+    let mut context = resolve_variables(&template.variables, &values)?;
+    if let Some(images) = images.filter(|images| !images.is_empty()) {
+        context.insert(
+            CAPTIONS_VARIABLE.to_string(),
+            Value::Object(caption_context(&images).await?),
+        );
+    }
+    Ok(RenderedPrompt {
+        system_prompt: render_template(&template.system_prompt, &context)?,
+        user_prompt: render_template(&template.user_prompt, &context)?,
+    })
 }
 
-async fn read_prompt_templates() -> Result<Vec<PromptTemplates>, String> {
-    let path = prompt_templates_path().await?;
+#[tauri::command]
+pub async fn get_default_prompts() -> Result<DefaultPrompts, String> {
+    Ok(DefaultPrompts {
+        system_prompt: read_prompt_file(SYSTEM_PROMPT_FILE).await?,
+        user_prompt: read_prompt_file(USER_PROMPT_FILE).await?,
+    })
+}
 
-    if !try_exists(&path)
-        .await
-        .map_err(|err| format!("Failed to check prompts file '{}': {}", path.display(), err))?
-    {
-        fs::write(&path, "[]").await.map_err(|err| {
-            format!(
-                "Unable to create prompts file '{}': {}",
-                path.display(),
-                err
-            )
-        })?;
-        return Ok(Vec::new());
+#[tauri::command]
+pub async fn set_default_prompts(
+    payload: SetDefaultPromptsPayload,
+) -> Result<DefaultPrompts, String> {
+    if let Some(system_prompt) = payload.system_prompt {
+        write_prompt_file(SYSTEM_PROMPT_FILE, &system_prompt).await?;
+    }
+    if let Some(user_prompt) = payload.user_prompt {
+        write_prompt_file(USER_PROMPT_FILE, &user_prompt).await?;
     }
+    get_default_prompts().await
+}
 
-    let contents = fs::read_to_string(&path)
+/// The default system prompt, for requests that don't supply one. A missing
+/// or unreadable file means there is no default.
+pub(crate) async fn default_system_prompt() -> Option<String> {
+    read_prompt_file(SYSTEM_PROMPT_FILE)
         .await
-        .map_err(|err| format!("Unable to read prompts file '{}': {}", path.display(), err))?;
+        .map_err(|err| eprintln!("Ignoring default system prompt: {err}"))
+        .ok()
+        .map(|prompt| prompt.trim().to_string())
+        .filter(|prompt| !prompt.is_empty())
+}
 
-    if contents.trim().is_empty() {
-        return Ok(Vec::new());
-    }
+#[tauri::command]
+pub async fn list_prompt_templates() -> Result<Vec<PromptTemplates>, String> {
+    read_prompt_templates().await
+}
 
-    serde_json::from_str(&contents)
-        .map_err(|err| format!("Unable to parse prompts file '{}': {}", path.display(), err))
+async fn prompt_templates_path() -> Result<PathBuf, String> {
+    let dir = ensure_input_dir().await?;
+    Ok(dir.join(PROMPT_TEMPLATES_FILE))
 }
 
-async fn write_prompt_templates(templates: &[PromptTemplates]) -> Result<(), String> {
-    let path = prompt_templates_path().await?;
-    let payload = serde_json::to_string_pretty(templates)
-        .map_err(|err| format!("Unable to serialise prompt templates: {}", err))?;
+pub(crate) async fn read_prompt_templates() -> Result<Vec<PromptTemplates>, String> {
+    store::load(&prompt_templates_path().await?).await
+}
 
-    fs::write(&path, payload)
-        .await
-        .map_err(|err| format!("Unable to write prompts file '{}': {}", path.display(), err))
+/// Loads the templates for a change; the prompts file stays locked until the
+/// returned document is dropped, so call `save` before then.
+pub(crate) async fn open_prompt_templates() -> Result<Document<Vec<PromptTemplates>>, String> {
+    store::open(&prompt_templates_path().await?).await
 }
 
-fn generate_unique_id(existing: &[PromptTemplates]) -> String {
+pub(crate) fn generate_unique_id(existing: &[PromptTemplates]) -> String {
     let mut counter = 0u64;
     loop {
         let candidate = SystemTime::now()
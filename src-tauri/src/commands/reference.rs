@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use tokio::fs;
+
+use crate::annotate::draw_annotations;
+use crate::fs_utils::{
+    build_stored_image, ensure_input_dir, ensure_output_dir, ensure_unique_file_name,
+    resolve_library_path, sanitize_file_name,
+};
+use crate::image_utils::{decode_image, encode_image};
+use crate::models::{PrepareReferenceRequest, StoredImage};
+
+/// Derives a new reference in `input/` from an input or output image: crop,
+/// then rotate and flip, then cap the size, then draw annotations. The source
+/// file is never modified.
+#[tauri::command]
+pub async fn prepare_reference_image(
+    payload: PrepareReferenceRequest,
+) -> Result<StoredImage, String> {
+    let dir = match payload
+        .source_library
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or("input")
+    {
+        "input" | "" => ensure_input_dir().await?,
+        "output" => ensure_output_dir().await?,
+        other => return Err(format!("Unknown image library '{}'.", other)),
+    };
+    let source_path = resolve_library_path(&dir, &payload.id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", payload.id))?;
+    let source = fs::read(&source_path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", payload.id, err))?;
+
+    let file_name = match payload.file_name.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => sanitize_file_name(name)
+            .ok_or_else(|| format!("Invalid file name supplied: {}", name))?,
+        _ => {
+            let stem = source_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("image");
+            format!("{stem}_ref")
+        }
+    };
+    let file_name = Path::new(&file_name)
+        .with_extension("png")
+        .to_string_lossy()
+        .into_owned();
+
+    let bytes = tokio::task::spawn_blocking(move || transform_reference(&source, payload))
+        .await
+        .map_err(|err| format!("Reference preparation task failed: {}", err))??;
+
+    let input_dir = ensure_input_dir().await?;
+    let unique_name = ensure_unique_file_name(&input_dir, &file_name).await?;
+    let target_path = input_dir.join(&unique_name);
+    fs::write(&target_path, &bytes)
+        .await
+        .map_err(|err| format!("Unable to write file '{}': {}", unique_name, err))?;
+
+    build_stored_image(&target_path, bytes.len() as u64, None).await
+}
+
+fn transform_reference(source: &[u8], payload: PrepareReferenceRequest) -> Result<Vec<u8>, String> {
+    let mut image = decode_image(source)?;
+
+    if let Some(crop) = payload.crop {
+        let fits = crop.width > 0
+            && crop.height > 0
+            && crop
+                .x
+                .checked_add(crop.width)
+                .is_some_and(|right| right <= image.width())
+            && crop
+                .y
+                .checked_add(crop.height)
+                .is_some_and(|bottom| bottom <= image.height());
+        if !fits {
+            return Err(format!(
+                "Crop rectangle {}x{} at ({}, {}) does not fit the {}x{} image.",
+                crop.width,
+                crop.height,
+                crop.x,
+                crop.y,
+                image.width(),
+                image.height()
+            ));
+        }
+        image = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+
+    image = match payload.rotate.rem_euclid(360) {
+        0 => image,
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => {
+            return Err(format!(
+                "Rotation must be a multiple of 90 degrees, got {}.",
+                payload.rotate
+            ))
+        }
+    };
+    if payload.flip_horizontal {
+        image = image.fliph();
+    }
+    if payload.flip_vertical {
+        image = image.flipv();
+    }
+
+    let mut scale = 1.0;
+    if let Some(max_dimension) = payload.max_dimension.filter(|max| *max > 0) {
+        if image.width().max(image.height()) > max_dimension {
+            let original_width = image.width();
+            image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+            scale = image.width() as f32 / original_width as f32;
+        }
+    }
+
+    if !payload.annotations.is_empty() {
+        let mut canvas = image.to_rgba8();
+        draw_annotations(&mut canvas, &payload.annotations, scale)?;
+        image = DynamicImage::ImageRgba8(canvas);
+    }
+
+    encode_image(&image, ImageFormat::Png)
+}
@@ -0,0 +1,567 @@
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use llmapi::providers::gemini::send_generate_request_with_config;
+use llmapi::types::{LLMClient, LLMMessage, LLMMessageType, LLMProvider, LLMType};
+use llmapi::utils::content_hash;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::fs::try_exists;
+
+use crate::commands::generate::{
+    append_generation_log, extract_generated_image, persist_output_image, plan_output_name,
+    GenerationLogEntry,
+};
+use crate::constants::{DEFAULT_GEMINI_ENDPOINT, DEFAULT_IMAGE_MODEL};
+use crate::fs_utils::{
+    create_unique_dir, default_extension_for_mime, ensure_output_dir, ensure_sessions_dir,
+    resolve_library_path,
+};
+use crate::image_metadata::{
+    embed_metadata, generator_name, GenerationMetadata, GenerationParameters,
+};
+use crate::image_utils::{fit_image_to_budget, reference_image_budget};
+use crate::lineage::GenerationOperation;
+use crate::llm::{require_api_key, resolve_model};
+use crate::models::{
+    ContinueEditSessionRequest, OutputNamingOptions, ReferenceImagePayload,
+    StartEditSessionRequest, StoredImage,
+};
+use crate::naming::slugify;
+use crate::store::{self, Document};
+
+const SESSION_FILE_NAME: &str = "session.json";
+const SESSION_FILE_NAME_TEMPLATE: &str = "{project}_v{index:3}";
+/// Turns at the end of the history whose images are resent; two exchanges
+/// cover the latest version and the request that produced it.
+const HISTORY_IMAGE_TURNS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionRole {
+    User,
+    Model,
+}
+
+/// An image part of a turn, stored next to `session.json` so the history can
+/// be replayed even if the output copy is deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionImage {
+    pub file: String,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSessionTurn {
+    pub role: SessionRole,
+    pub text: Option<String>,
+    #[serde(default)]
+    pub images: Vec<SessionImage>,
+    pub version: Option<u32>,
+    pub output_image: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSession {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub system_prompt: Option<String>,
+    pub naming: Option<OutputNamingOptions>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub turns: Vec<EditSessionTurn>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSessionSummary {
+    pub id: String,
+    pub title: String,
+    pub model: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub version_count: u32,
+    pub latest_output: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditSessionTurnResponse {
+    pub session: EditSessionSummary,
+    pub version: u32,
+    pub image: StoredImage,
+    pub revised_prompt: Option<String>,
+}
+
+impl EditSession {
+    fn summary(&self) -> EditSessionSummary {
+        let latest = self
+            .turns
+            .iter()
+            .rev()
+            .find_map(|turn| turn.version.zip(turn.output_image.clone()));
+
+        EditSessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            model: self.model.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            version_count: latest.as_ref().map(|(version, _)| *version).unwrap_or(0),
+            latest_output: latest.map(|(_, output)| output),
+        }
+    }
+
+    fn next_version(&self) -> u32 {
+        self.turns
+            .iter()
+            .filter_map(|turn| turn.version)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+}
+
+/// Image bytes waiting to be written into the session directory once the
+/// provider call has succeeded.
+struct PendingImage {
+    file: String,
+    mime_type: String,
+    bytes: Vec<u8>,
+}
+
+#[tauri::command]
+pub async fn start_edit_session(
+    payload: StartEditSessionRequest,
+) -> Result<EditSessionTurnResponse, String> {
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+    let now = unix_timestamp();
+
+    let mut references = payload.reference_images;
+    let base_id = payload
+        .base_image_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    if let Some(base_id) = base_id {
+        let path = resolve_library_path(&ensure_output_dir().await?, base_id)
+            .ok_or_else(|| format!("Invalid image id supplied: {}", base_id))?;
+        let bytes = fs::read(&path)
+            .await
+            .map_err(|err| format!("Unable to read image '{}': {}", base_id, err))?;
+        references.insert(
+            0,
+            ReferenceImagePayload {
+                mime_type: None,
+                data_base64: BASE64_ENGINE.encode(bytes),
+                slot: Some("base".to_string()),
+                file_name: Some(base_id.to_string()),
+            },
+        );
+    }
+
+    // The directory is claimed before the provider call, so two sessions
+    // started in the same second never share an id.
+    let sessions_dir = ensure_sessions_dir().await?;
+    let id = create_unique_dir(&sessions_dir, &format!("session_{}", now)).await?;
+    let dir = sessions_dir.join(&id);
+
+    let title = payload
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| id.clone());
+
+    let result = async {
+        let mut session = store::open::<EditSession>(&dir.join(SESSION_FILE_NAME)).await?;
+        *session = EditSession {
+            id,
+            title,
+            model: resolve_model(payload.model.as_deref(), DEFAULT_IMAGE_MODEL),
+            system_prompt: payload
+                .system_prompt
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string),
+            naming: payload.naming,
+            created_at: now,
+            updated_at: now,
+            turns: Vec::new(),
+        };
+
+        let parent = base_id.map(str::to_string);
+        run_session_turn(
+            &mut session,
+            api_key,
+            &payload.instruction,
+            &references,
+            parent,
+        )
+        .await
+    }
+    .await;
+
+    if result.is_err() {
+        if let Err(err) = fs::remove_dir_all(&dir).await {
+            eprintln!(
+                "Unable to remove failed session '{}': {}",
+                dir.display(),
+                err
+            );
+        }
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn continue_edit_session(
+    payload: ContinueEditSessionRequest,
+) -> Result<EditSessionTurnResponse, String> {
+    let api_key = require_api_key(payload.api_key.as_deref())?;
+    let mut session = open_session(&payload.session_id).await?;
+    let parent = session.summary().latest_output;
+
+    run_session_turn(
+        &mut session,
+        api_key,
+        &payload.instruction,
+        &payload.reference_images,
+        parent,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn list_edit_sessions() -> Result<Vec<EditSessionSummary>, String> {
+    let dir = ensure_sessions_dir().await?;
+    let mut entries = fs::read_dir(&dir)
+        .await
+        .map_err(|err| format!("Unable to read directory '{}': {}", dir.display(), err))?;
+
+    let mut summaries = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|err| format!("Failed to iterate directory '{}': {}", dir.display(), err))?
+    {
+        let Ok(id) = entry.file_name().into_string() else {
+            continue;
+        };
+        match load_session(&id).await {
+            Ok(session) => summaries.push(session.summary()),
+            Err(err) => eprintln!("Skipping unreadable session '{id}': {err}"),
+        }
+    }
+
+    summaries.sort_by_key(|summary| Reverse(summary.updated_at));
+    Ok(summaries)
+}
+
+#[tauri::command]
+pub async fn get_edit_session(id: String) -> Result<EditSession, String> {
+    load_session(&id).await
+}
+
+#[tauri::command]
+pub async fn delete_edit_session(id: String) -> Result<(), String> {
+    let dir = session_dir(&id).await?;
+    if !try_exists(&dir)
+        .await
+        .map_err(|err| format!("Failed to check session '{}': {}", id, err))?
+    {
+        return Ok(());
+    }
+
+    fs::remove_dir_all(&dir)
+        .await
+        .map_err(|err| format!("Failed to delete session '{}': {}", id, err))
+}
+
+/// Sends the whole conversation plus the new instruction, then records both
+/// turns, saves the reply as the next output version and persists the session.
+/// `parent` is the output this version refines, for lineage. The session stays
+/// locked for the whole turn, so overlapping turns queue up instead of
+/// overwriting each other's history.
+async fn run_session_turn(
+    session: &mut Document<EditSession>,
+    api_key: &str,
+    instruction: &str,
+    references: &[ReferenceImagePayload],
+    parent: Option<String>,
+) -> Result<EditSessionTurnResponse, String> {
+    let instruction = instruction.trim();
+    if instruction.is_empty() {
+        return Err("Edit instruction cannot be empty.".into());
+    }
+
+    let dir = session_dir(&session.id).await?;
+    let turn_index = session.turns.len();
+    let budget = reference_image_budget(LLMProvider::Gemini);
+
+    let mut pending = Vec::new();
+    let mut reference_hashes = Vec::new();
+    for (index, reference) in references.iter().enumerate() {
+        let data = reference.data_base64.trim();
+        if data.is_empty() {
+            continue;
+        }
+        let original = BASE64_ENGINE
+            .decode(data)
+            .map_err(|err| format!("Failed to decode reference image {}: {}", index, err))?;
+        let prepared = fit_image_to_budget(&original, &budget)
+            .map_err(|err| format!("Invalid reference image {}: {}", index, err))?;
+        // Hash the upload as supplied, like `generate_image`, so metadata
+        // imports can find the reference again in the input library.
+        reference_hashes.push(content_hash(&original));
+
+        pending.push(PendingImage {
+            file: format!("turn{:03}_{}.{}", turn_index, index, prepared.extension()),
+            mime_type: prepared.mime_type().to_string(),
+            bytes: prepared.bytes,
+        });
+    }
+
+    let mut messages = build_session_messages(session, &dir).await?;
+    let mut user_content: Vec<LLMMessageType> = pending
+        .iter()
+        .map(|image| LLMMessageType::IMAGE {
+            data_b64: BASE64_ENGINE.encode(&image.bytes),
+            file_path: Some(image.file.clone()),
+        })
+        .collect();
+    user_content.push(LLMMessageType::text(instruction.to_string()));
+    messages.push(LLMMessage::new(None, "Human", user_content));
+
+    let version = session.next_version();
+    let naming = session
+        .naming
+        .clone()
+        .unwrap_or_else(|| OutputNamingOptions {
+            file_name_template: Some(SESSION_FILE_NAME_TEMPLATE.to_string()),
+            project: Some(session_slug(session)),
+            ..OutputNamingOptions::default()
+        });
+    let output_name = plan_output_name(
+        Some(&naming),
+        instruction,
+        &session.model,
+        None,
+        version as usize,
+    )?;
+
+    let client = LLMClient::new(
+        LLMProvider::Gemini,
+        api_key,
+        DEFAULT_GEMINI_ENDPOINT,
+        session.model.clone(),
+        LLMType::Chat,
+    );
+    let response = send_generate_request_with_config(&client, messages, None)
+        .await
+        .map_err(|err| format!("Failed to request image edit: {}", err))?;
+    let generated = extract_generated_image(response)?;
+    let bytes = BASE64_ENGINE
+        .decode(generated.base64.trim())
+        .map_err(|err| format!("Failed to decode generated image: {}", err))?;
+
+    let now = unix_timestamp();
+    let extension =
+        default_extension_for_mime(&generated.mime_type).unwrap_or_else(|| "bin".to_string());
+    pending.push(PendingImage {
+        file: format!("turn{:03}_v{}.{}", turn_index + 1, version, extension),
+        mime_type: generated.mime_type.clone(),
+        bytes: bytes.clone(),
+    });
+    let model_image = pending.last().map(|image| SessionImage {
+        file: image.file.clone(),
+        mime_type: image.mime_type.clone(),
+    });
+
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|err| format!("Unable to create session directory: {}", err))?;
+    for image in &pending {
+        fs::write(dir.join(&image.file), &image.bytes)
+            .await
+            .map_err(|err| format!("Unable to save session image: {}", err))?;
+    }
+
+    let metadata = GenerationMetadata {
+        generator: generator_name(),
+        prompt: instruction.to_string(),
+        system_prompt: session.system_prompt.clone(),
+        model: session.model.clone(),
+        parameters: GenerationParameters::default(),
+        seed: None,
+        reference_hashes,
+        reference_slots: Vec::new(),
+        created_at: now,
+    };
+    let output_bytes = embed_metadata(bytes.clone(), &metadata).unwrap_or_else(|err| {
+        eprintln!("Saving session image without metadata: {err}");
+        bytes
+    });
+
+    let stored_image =
+        persist_output_image(&output_bytes, &generated.mime_type, &output_name).await?;
+
+    session.turns.push(EditSessionTurn {
+        role: SessionRole::User,
+        text: Some(instruction.to_string()),
+        images: pending[..pending.len() - 1]
+            .iter()
+            .map(|image| SessionImage {
+                file: image.file.clone(),
+                mime_type: image.mime_type.clone(),
+            })
+            .collect(),
+        version: None,
+        output_image: None,
+        created_at: now,
+    });
+    session.turns.push(EditSessionTurn {
+        role: SessionRole::Model,
+        text: generated.revised_prompt.clone(),
+        images: model_image.into_iter().collect(),
+        version: Some(version),
+        output_image: Some(stored_image.id.clone()),
+        created_at: now,
+    });
+    session.updated_at = now;
+    session
+        .save()
+        .await
+        .map_err(|err| format!("Failed to write session: {}", err))?;
+
+    append_generation_log(GenerationLogEntry {
+        timestamp: now,
+        prompt: instruction.to_string(),
+        system_prompt: session.system_prompt.clone(),
+        reference_images: Vec::new(),
+        reference_slots: Vec::new(),
+        output_image: format!("output/{}", stored_image.id),
+        post_process: None,
+        operation: GenerationOperation::Refine,
+        parents: parent
+            .map(|id| format!("output/{id}"))
+            .into_iter()
+            .collect(),
+        template: None,
+    })
+    .await?;
+
+    Ok(EditSessionTurnResponse {
+        session: session.summary(),
+        version,
+        image: stored_image,
+        revised_prompt: generated.revised_prompt,
+    })
+}
+
+/// The session title as a file-name-safe `{project}` for the default naming
+/// template, falling back to the session id.
+fn session_slug(session: &EditSession) -> String {
+    Some(slugify(&session.title))
+        .filter(|slug| !slug.is_empty())
+        .unwrap_or_else(|| session.id.clone())
+}
+
+/// Rebuilds the provider conversation from the stored turns. Only the last
+/// `HISTORY_IMAGE_TURNS` turns carry their images, fitted to the reference
+/// budget; older images are replaced by a note so the request size stays
+/// bounded however long the session runs.
+async fn build_session_messages(
+    session: &EditSession,
+    dir: &Path,
+) -> Result<Vec<LLMMessage>, String> {
+    let mut messages = Vec::new();
+    let budget = reference_image_budget(LLMProvider::Gemini);
+    let first_image_turn = session.turns.len().saturating_sub(HISTORY_IMAGE_TURNS);
+
+    if let Some(system_prompt) = session.system_prompt.as_deref() {
+        messages.push(LLMMessage::new(
+            None,
+            "Human",
+            vec![LLMMessageType::text(system_prompt.to_string())],
+        ));
+    }
+
+    for (index, turn) in session.turns.iter().enumerate() {
+        let mut content = Vec::new();
+        if index < first_image_turn {
+            if !turn.images.is_empty() {
+                content.push(LLMMessageType::text(format!(
+                    "[{} earlier image(s) omitted]",
+                    turn.images.len()
+                )));
+            }
+        } else {
+            for image in &turn.images {
+                let bytes = fs::read(dir.join(&image.file))
+                    .await
+                    .map_err(|err| format!("Missing session image '{}': {}", image.file, err))?;
+                let prepared = fit_image_to_budget(&bytes, &budget)
+                    .map_err(|err| format!("Invalid session image '{}': {}", image.file, err))?;
+                content.push(LLMMessageType::IMAGE {
+                    data_b64: BASE64_ENGINE.encode(&prepared.bytes),
+                    file_path: Some(image.file.clone()),
+                });
+            }
+        }
+        if let Some(text) = turn.text.as_deref().filter(|text| !text.is_empty()) {
+            content.push(LLMMessageType::text(text.to_string()));
+        }
+        if content.is_empty() {
+            continue;
+        }
+
+        let role = match turn.role {
+            SessionRole::User => "Human",
+            SessionRole::Model => "AI",
+        };
+        messages.push(LLMMessage::new(None, role, content));
+    }
+
+    Ok(messages)
+}
+
+async fn session_dir(id: &str) -> Result<PathBuf, String> {
+    resolve_library_path(&ensure_sessions_dir().await?, id)
+        .filter(|_| !id.contains('/'))
+        .ok_or_else(|| format!("Invalid session id supplied: {}", id))
+}
+
+async fn load_session(id: &str) -> Result<EditSession, String> {
+    let path = session_dir(id).await?.join(SESSION_FILE_NAME);
+    store::load_optional(&path)
+        .await
+        .map_err(|err| format!("Unable to read session '{}': {}", id, err))?
+        .ok_or_else(|| format!("Session '{}' not found.", id))
+}
+
+/// Locks and loads a session for a turn; see `run_session_turn`.
+async fn open_session(id: &str) -> Result<Document<EditSession>, String> {
+    let path = session_dir(id).await?.join(SESSION_FILE_NAME);
+    store::open_optional(&path)
+        .await
+        .map_err(|err| format!("Unable to read session '{}': {}", id, err))?
+        .ok_or_else(|| format!("Session '{}' not found.", id))
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
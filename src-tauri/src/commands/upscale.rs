@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use image::ImageFormat;
+use tokio::fs;
+
+use crate::commands::generate::record_derived_output;
+use crate::constants::MAX_OUTPUT_DIMENSION;
+use crate::fs_utils::{ensure_output_dir, resolve_library_path, write_beside_output};
+use crate::image_metadata::{embed_metadata, extract_metadata};
+use crate::image_utils::{decode_image, encode_image};
+use crate::lineage::GenerationOperation;
+use crate::models::{GeneratedImageResponsePayload, UpscaleImageRequest};
+use crate::onnx::run_super_resolution;
+use crate::post_process::filter_type;
+
+const DEFAULT_UPSCALE_FACTOR: f32 = 2.0;
+const MAX_UPSCALE_FACTOR: f32 = 8.0;
+const DEFAULT_SHARPEN_THRESHOLD: i32 = 2;
+
+/// Upscales an output with a resampling filter, or with a user-supplied ONNX
+/// super-resolution model when `model_path` is set, then optionally applies
+/// an unsharp mask. The result is saved as a PNG beside the source.
+#[tauri::command]
+pub async fn upscale_image(
+    payload: UpscaleImageRequest,
+) -> Result<GeneratedImageResponsePayload, String> {
+    let source_path = resolve_library_path(&ensure_output_dir().await?, &payload.id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", payload.id))?;
+    let source = fs::read(&source_path)
+        .await
+        .map_err(|err| format!("Unable to read image '{}': {}", payload.id, err))?;
+    let metadata = extract_metadata(&source).unwrap_or(None);
+
+    let model_path = payload
+        .model_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    let scale = payload.scale;
+    let target_width = payload.width;
+    let filter = filter_type(payload.filter);
+    let sharpen = payload
+        .sharpen_sigma
+        .filter(|sigma| *sigma > 0.0)
+        .map(|sigma| {
+            (
+                sigma,
+                payload
+                    .sharpen_threshold
+                    .unwrap_or(DEFAULT_SHARPEN_THRESHOLD),
+            )
+        });
+
+    // Decoding, resampling and inference are CPU bound; keep them off the
+    // async runtime.
+    let bytes = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let image = decode_image(&source)?;
+
+        let factor = match (target_width, scale) {
+            (Some(width), _) => width as f32 / image.width() as f32,
+            (None, Some(scale)) => scale,
+            (None, None) => DEFAULT_UPSCALE_FACTOR,
+        };
+        if !(factor > 1.0 && factor <= MAX_UPSCALE_FACTOR) {
+            return Err(format!(
+                "Upscale factor must be above 1 and at most {}.",
+                MAX_UPSCALE_FACTOR
+            ));
+        }
+        let width = (image.width() as f32 * factor).round() as u32;
+        let height = (image.height() as f32 * factor).round() as u32;
+        if width.max(height) > MAX_OUTPUT_DIMENSION {
+            return Err(format!(
+                "Upscaling to {}x{} exceeds the {} px limit; use a smaller factor.",
+                width, height, MAX_OUTPUT_DIMENSION
+            ));
+        }
+
+        let mut upscaled = match &model_path {
+            Some(path) => run_super_resolution(path, &image)?,
+            None => image.resize_exact(width, height, filter),
+        };
+        // Models have a fixed scale; bring their output to the requested size.
+        if upscaled.width() != width || upscaled.height() != height {
+            upscaled = upscaled.resize_exact(width, height, filter);
+        }
+
+        if let Some((sigma, threshold)) = sharpen {
+            upscaled = upscaled.unsharpen(sigma, threshold);
+        }
+
+        encode_image(&upscaled, ImageFormat::Png)
+    })
+    .await
+    .map_err(|err| format!("Upscale task failed: {}", err))??;
+
+    let bytes = match &metadata {
+        Some(metadata) => embed_metadata(bytes.clone(), metadata).unwrap_or(bytes),
+        None => bytes,
+    };
+
+    let stored_image = write_beside_output(&payload.id, "_upscaled", "png", &bytes).await?;
+    record_derived_output(
+        &payload.id,
+        &stored_image.id,
+        GenerationOperation::Upscale,
+        metadata.as_ref(),
+    )
+    .await?;
+
+    Ok(GeneratedImageResponsePayload {
+        image: stored_image,
+        revised_prompt: None,
+    })
+}
@@ -0,0 +1,108 @@
+use tokio::task::JoinSet;
+
+use crate::commands::generate::{run_generation, upload_references};
+use crate::commands::lineage::build_branch_request;
+use crate::lineage::GenerationOperation;
+use crate::llm::require_api_key;
+use crate::models::{GenerateVariationsRequest, GenerateVariationsResponse, OutputNamingOptions};
+
+const MAX_VARIATIONS: u32 = 8;
+const DEFAULT_VARIATION_STRENGTH: f32 = 0.5;
+const VARIATION_FILE_NAME_TEMPLATE: &str = "image_{timestamp}_{index}";
+
+/// Generates `count` variations of an output in parallel. Each run reuses the
+/// source's prompt and references from history, adds the source image itself
+/// as a reference and is recorded as its child in the lineage.
+#[tauri::command]
+pub async fn generate_variations(
+    payload: GenerateVariationsRequest,
+) -> Result<GenerateVariationsResponse, String> {
+    if payload.count == 0 || payload.count > MAX_VARIATIONS {
+        return Err(format!(
+            "Variation count must be between 1 and {}.",
+            MAX_VARIATIONS
+        ));
+    }
+
+    let api_key = require_api_key(payload.api_key.as_deref())?.to_string();
+    let strength = payload
+        .strength
+        .unwrap_or(DEFAULT_VARIATION_STRENGTH)
+        .clamp(0.0, 1.0);
+
+    let mut base = build_branch_request(&payload.output_id).await?;
+    base.api_key = Some(api_key);
+    if payload.model.is_some() {
+        base.model = payload.model.clone();
+    }
+    base.naming = Some(
+        payload
+            .naming
+            .clone()
+            .unwrap_or_else(|| OutputNamingOptions {
+                file_name_template: Some(VARIATION_FILE_NAME_TEMPLATE.to_string()),
+                ..OutputNamingOptions::default()
+            }),
+    );
+
+    upload_references(&base).await?;
+
+    // Sent alongside the prompt rather than appended to it, so the recorded
+    // prompt stays the source's and variations of variations don't stack
+    // instructions.
+    let instruction = variation_instruction(strength);
+    let mut tasks = JoinSet::new();
+    for index in 1..=payload.count as usize {
+        let mut request = base.clone();
+        let instruction = instruction.clone();
+        // Keep a recorded seed reproducible while still varying each run.
+        request.seed = base.seed.map(|seed| seed.wrapping_add(index as u32));
+        tasks.spawn(async move {
+            let result = run_generation(
+                request,
+                GenerationOperation::Variation,
+                index,
+                Some(instruction),
+            )
+            .await;
+            (index, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.map_err(|err| format!("Variation task failed: {}", err))?);
+    }
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut images = Vec::new();
+    let mut failures = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok(image) => images.push(image),
+            Err(err) => failures.push(format!("Variation {}: {}", index, err)),
+        }
+    }
+
+    if images.is_empty() {
+        return Err(failures.join("\n"));
+    }
+
+    Ok(GenerateVariationsResponse { images, failures })
+}
+
+fn variation_instruction(strength: f32) -> String {
+    let guidance = if strength < 0.34 {
+        "Stay very close to it: keep the composition, subject, palette and framing, changing only small details."
+    } else if strength < 0.67 {
+        "Keep the subject and overall style but vary pose, composition and secondary details."
+    } else {
+        "Treat it as loose inspiration: keep the core idea but freely explore composition, colour and style."
+    };
+
+    format!(
+        "Create a new variation of the last reference image (variation strength {:.0}%). {}",
+        strength * 100.0,
+        guidance
+    )
+}
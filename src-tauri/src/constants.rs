@@ -1,8 +1,31 @@
 pub const DEFAULT_IMAGE_MODEL: &str = "gemini-2.5-flash-image";
+/// Gemini models known to return images from `generateContent`.
+pub const GEMINI_IMAGE_MODELS: &[&str] = &[
+    "gemini-2.5-flash-image",
+    "gemini-2.5-flash-image-preview",
+    "gemini-2.0-flash-preview-image-generation",
+    "gemini-3-pro-image-preview",
+];
+/// Aspect ratios Gemini image models follow when asked in the prompt.
+pub const GEMINI_ASPECT_RATIOS: &[&str] = &[
+    "1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9",
+];
 pub const DEFAULT_GEMINI_ENDPOINT: &str = "https://generativelanguage.googleapis.com/v1beta/models";
 pub const DEFAULT_IMAGE_MIME: &str = "image/png";
 pub const INPUT_DIR_NAME: &str = "input";
 pub const OUTPUT_DIR_NAME: &str = "output";
+pub const CACHE_DIR_NAME: &str = "cache";
+pub const SESSIONS_DIR_NAME: &str = "sessions";
 pub const PROMPTS_DIR_NAME: &str = "prompts";
 pub const SYSTEM_PROMPT_FILE: &str = "system_prompt.txt";
 pub const USER_PROMPT_FILE: &str = "instructions.txt";
+pub const DEFAULT_MAX_UPLOAD_DIMENSION: u32 = 3072;
+pub const MAX_OUTPUT_DIMENSION: u32 = 8192;
+pub const GEMINI_FILE_UPLOAD_THRESHOLD: usize = 1024 * 1024;
+pub const GEMINI_FILE_CACHE_FILE: &str = "gemini_files.json";
+pub const DEFAULT_OPENAI_ENDPOINT: &str = "https://api.openai.com/v1";
+pub const DEFAULT_ANTHROPIC_ENDPOINT: &str = "https://api.anthropic.com/v1";
+pub const DEFAULT_OPENAI_EDIT_MODEL: &str = "gpt-image-1";
+pub const DEFAULT_GEMINI_CHAT_MODEL: &str = "gemini-2.5-flash";
+pub const DEFAULT_OPENAI_CHAT_MODEL: &str = "gpt-4o-mini";
+pub const DEFAULT_ANTHROPIC_CHAT_MODEL: &str = "claude-sonnet-4-5";
@@ -1,3 +1,4 @@
+use std::cmp::Reverse;
 use std::path::{Path, PathBuf};
 use std::time::UNIX_EPOCH;
 
@@ -5,8 +6,11 @@ use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
 use base64::Engine;
 use tokio::fs;
 use tokio::fs::try_exists;
+use tokio::io::AsyncWriteExt;
 
-use crate::constants::{INPUT_DIR_NAME, OUTPUT_DIR_NAME, PROMPTS_DIR_NAME};
+use crate::constants::{
+    CACHE_DIR_NAME, INPUT_DIR_NAME, OUTPUT_DIR_NAME, PROMPTS_DIR_NAME, SESSIONS_DIR_NAME,
+};
 use crate::models::StoredImage;
 
 use std::io;
@@ -20,36 +24,89 @@ pub async fn ensure_input_dir() -> Result<PathBuf, String> {
     ensure_library_dir(INPUT_DIR_NAME).await
 }
 
-pub async fn ensure_unique_file_name(dir: &Path, original: &str) -> Result<String, String> {
-    if !try_exists(dir.join(original))
-        .await
-        .map_err(|err| format!("Failed to verify file existence: {}", err))?
-    {
-        return Ok(original.to_string());
-    }
-
-    let original_path = Path::new(original);
-    let stem = original_path
-        .file_stem()
-        .and_then(|stem| stem.to_str())
-        .unwrap_or("image");
-    let extension = original_path.extension().and_then(|ext| ext.to_str());
+pub async fn ensure_cache_dir() -> Result<PathBuf, String> {
+    ensure_library_dir(CACHE_DIR_NAME).await
+}
 
-    let mut counter = 1;
-    loop {
-        let candidate = match extension {
-            Some(ext) => format!("{stem}-{counter}.{ext}"),
-            None => format!("{stem}-{counter}"),
-        };
+pub async fn ensure_sessions_dir() -> Result<PathBuf, String> {
+    ensure_library_dir(SESSIONS_DIR_NAME).await
+}
 
+pub async fn ensure_unique_file_name(dir: &Path, original: &str) -> Result<String, String> {
+    for counter in 0.. {
+        let candidate = numbered_file_name(original, counter);
         if !try_exists(dir.join(&candidate))
             .await
             .map_err(|err| format!("Failed to verify file existence: {}", err))?
         {
             return Ok(candidate);
         }
+    }
+    unreachable!("the counter never runs out")
+}
+
+/// Writes `bytes` as `original` in `dir`, or under the first free
+/// `<stem>-<n>` name. Each name is claimed with `create_new`, so concurrent
+/// writers picking the same name never overwrite each other.
+pub async fn write_unique_file(dir: &Path, original: &str, bytes: &[u8]) -> Result<String, String> {
+    for counter in 0.. {
+        let candidate = numbered_file_name(original, counter);
+        let path = dir.join(&candidate);
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Unable to create '{}': {}", path.display(), err)),
+        };
+
+        let written = async {
+            file.write_all(bytes).await?;
+            file.flush().await
+        }
+        .await;
+        if let Err(err) = written {
+            drop(file);
+            let _ = fs::remove_file(&path).await;
+            return Err(format!("Unable to write '{}': {}", path.display(), err));
+        }
+        return Ok(candidate);
+    }
+    unreachable!("the counter never runs out")
+}
 
-        counter += 1;
+/// Creates the directory `original` in `dir`, or the first free
+/// `<name>-<n>`, and returns its name. Each name is claimed with `create_dir`,
+/// so concurrent callers never end up sharing one.
+pub async fn create_unique_dir(dir: &Path, original: &str) -> Result<String, String> {
+    for counter in 0.. {
+        let candidate = numbered_file_name(original, counter);
+        match fs::create_dir(dir.join(&candidate)).await {
+            Ok(()) => return Ok(candidate),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Unable to create '{}': {}", candidate, err)),
+        }
+    }
+    unreachable!("the counter never runs out")
+}
+
+/// `original` for counter 0, otherwise `<stem>-<counter>.<ext>`.
+fn numbered_file_name(original: &str, counter: u32) -> String {
+    if counter == 0 {
+        return original.to_string();
+    }
+
+    let original_path = Path::new(original);
+    let stem = original_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("image");
+    match original_path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{stem}-{counter}.{ext}"),
+        None => format!("{stem}-{counter}"),
     }
 }
 
@@ -108,56 +165,78 @@ pub async fn write_prompt_file(file_name: &str, contents: &str) -> Result<(), St
         .map_err(|err| format!("Unable to write prompt file '{}': {}", path.display(), err))
 }
 
-pub async fn collect_directory_images(dir: &Path) -> Result<Vec<StoredImage>, String> {
+/// Lists the images in `dir`, newest first. With `recursive`, images in
+/// subfolders are included and their ids are `/`-separated relative paths.
+pub async fn collect_directory_images(
+    dir: &Path,
+    recursive: bool,
+) -> Result<Vec<StoredImage>, String> {
     let mut images_with_timestamp: Vec<(StoredImage, u128)> = Vec::new();
+    let mut pending: Vec<(PathBuf, String)> = vec![(dir.to_path_buf(), String::new())];
+
+    while let Some((current_dir, prefix)) = pending.pop() {
+        let mut entries = fs::read_dir(&current_dir).await.map_err(|err| {
+            format!(
+                "Unable to read directory '{}': {}",
+                current_dir.display(),
+                err
+            )
+        })?;
+
+        while let Some(entry) = entries.next_entry().await.map_err(|err| {
+            format!(
+                "Failed to iterate directory '{}': {}",
+                current_dir.display(),
+                err
+            )
+        })? {
+            let metadata = entry
+                .metadata()
+                .await
+                .map_err(|err| format!("Failed to read metadata: {}", err))?;
+
+            let path = entry.path();
+            let file_name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let relative_id = format!("{prefix}{file_name}");
+
+            if metadata.is_dir() {
+                if recursive {
+                    pending.push((path, format!("{relative_id}/")));
+                }
+                continue;
+            }
 
-    let mut entries = fs::read_dir(dir)
-        .await
-        .map_err(|err| format!("Unable to read directory '{}': {}", dir.display(), err))?;
-
-    while let Some(entry) = entries
-        .next_entry()
-        .await
-        .map_err(|err| format!("Failed to iterate directory '{}': {}", dir.display(), err))?
-    {
-        let metadata = entry
-            .metadata()
-            .await
-            .map_err(|err| format!("Failed to read metadata: {}", err))?;
-
-        if !metadata.is_file() {
-            continue;
-        }
-
-        let path = entry.path();
-        let file_name = match entry.file_name().into_string() {
-            Ok(name) => name,
-            Err(_) => continue,
-        };
+            if !metadata.is_file() {
+                continue;
+            }
 
-        let guessed_mime = resolve_mime_type(None, &path);
-        if !guessed_mime.starts_with("image/") {
-            continue;
-        }
+            let guessed_mime = resolve_mime_type(None, &path);
+            if !guessed_mime.starts_with("image/") {
+                continue;
+            }
 
-        let modified_time = metadata
-            .modified()
-            .ok()
-            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
-            .map(|duration| duration.as_nanos())
-            .unwrap_or(0);
-
-        match build_stored_image(&path, metadata.len(), Some(guessed_mime)).await {
-            Ok(mut image) => {
-                image.id = file_name.clone();
-                image.name = file_name;
-                images_with_timestamp.push((image, modified_time));
+            let modified_time = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos())
+                .unwrap_or(0);
+
+            match build_stored_image(&path, metadata.len(), Some(guessed_mime)).await {
+                Ok(mut image) => {
+                    image.id = relative_id;
+                    image.name = file_name;
+                    images_with_timestamp.push((image, modified_time));
+                }
+                Err(err) => return Err(err),
             }
-            Err(err) => return Err(err),
         }
     }
 
-    images_with_timestamp.sort_by(|a, b| b.1.cmp(&a.1));
+    images_with_timestamp.sort_by_key(|(_, modified)| Reverse(*modified));
 
     Ok(images_with_timestamp
         .into_iter()
@@ -171,11 +250,10 @@ pub async fn delete_from_directory(ids: Vec<String>, dir: PathBuf) -> Result<(),
     }
 
     for file_id in ids {
-        if !is_safe_file_name(&file_id) {
+        let Some(file_path) = resolve_library_path(&dir, &file_id) else {
             continue;
-        }
+        };
 
-        let file_path = dir.join(&file_id);
         if try_exists(&file_path)
             .await
             .map_err(|err| format!("Failed to check file '{}': {}", file_id, err))?
@@ -214,6 +292,35 @@ pub async fn build_stored_image(
     })
 }
 
+/// Writes `bytes` next to the output `source_id` as `<stem><suffix>.<extension>`
+/// and returns it with its `/`-separated library id.
+pub async fn write_beside_output(
+    source_id: &str,
+    suffix: &str,
+    extension: &str,
+    bytes: &[u8],
+) -> Result<StoredImage, String> {
+    let source = resolve_library_path(&ensure_output_dir().await?, source_id)
+        .ok_or_else(|| format!("Invalid image id supplied: {}", source_id))?;
+    let dir = source
+        .parent()
+        .ok_or_else(|| format!("Invalid image id supplied: {}", source_id))?;
+    let stem = source
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("image");
+
+    let file_name = write_unique_file(dir, &format!("{stem}{suffix}.{extension}"), bytes).await?;
+    let path = dir.join(&file_name);
+
+    let mut stored_image = build_stored_image(&path, bytes.len() as u64, None).await?;
+    stored_image.id = match source_id.rsplit_once('/') {
+        Some((folder, _)) => format!("{folder}/{file_name}"),
+        None => file_name,
+    };
+    Ok(stored_image)
+}
+
 pub fn default_extension_for_mime(mime_type: &str) -> Option<String> {
     let mime = mime_type.trim().to_lowercase();
     let ext = match mime.as_str() {
@@ -246,6 +353,18 @@ async fn ensure_library_dir(dir_name: &str) -> Result<PathBuf, String> {
     Ok(path)
 }
 
+/// Joins a `/`-separated id onto `dir`, rejecting anything that could escape it.
+pub fn resolve_library_path(dir: &Path, id: &str) -> Option<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for segment in id.split('/') {
+        if !is_safe_file_name(segment) {
+            return None;
+        }
+        path.push(segment);
+    }
+    Some(path)
+}
+
 fn is_safe_file_name(file_name: &str) -> bool {
     !file_name.is_empty()
         && !file_name.contains(['/', '\\'])
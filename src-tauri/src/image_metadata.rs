@@ -0,0 +1,586 @@
+use img_parts::jpeg::{markers, Jpeg, JpegSegment};
+use img_parts::png::{Png, PngChunk};
+use img_parts::riff::{RiffChunk, RiffContent};
+use img_parts::webp::{
+    WebP, CHUNK_ALPH, CHUNK_EXIF, CHUNK_ICCP, CHUNK_VP8L, CHUNK_VP8X, CHUNK_XMP,
+};
+use img_parts::{Bytes, DynImage, ImageICC};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::image_utils::detect_image_format;
+
+/// iTXt keyword (PNG) holding the JSON-encoded `GenerationMetadata`.
+pub const METADATA_KEYWORD: &str = "ai-image-gen";
+
+const PNG_SOFTWARE_KEYWORD: &str = "Software";
+const PNG_DESCRIPTION_KEYWORD: &str = "Description";
+/// Text chunks rewritten on every embed, so re-embedding never duplicates them.
+const REPLACED_PNG_KEYWORDS: &[&str] = &[
+    PNG_SOFTWARE_KEYWORD,
+    PNG_DESCRIPTION_KEYWORD,
+    METADATA_KEYWORD,
+];
+
+const XMP_NAMESPACE: &str = "https://github.com/acscoder-digitalonda/ai-image-gen/ns/1.0/";
+const XMP_JPEG_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Largest JPEG segment payload: the 16-bit length also counts itself.
+const MAX_JPEG_SEGMENT_CONTENTS: usize = u16::MAX as usize - 2;
+const PNG_TEXT: [u8; 4] = *b"tEXt";
+const PNG_ITXT: [u8; 4] = *b"iTXt";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationParameters {
+    pub size: Option<String>,
+    pub quality: Option<String>,
+    pub style: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationMetadata {
+    pub generator: String,
+    pub prompt: String,
+    pub system_prompt: Option<String>,
+    pub model: String,
+    #[serde(default)]
+    pub parameters: GenerationParameters,
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub reference_hashes: Vec<String>,
+    /// The slot each reference was sent under, so an imported prompt's
+    /// placeholders still point at the right image. Empty in older images.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference_slots: Vec<ReferenceSlot>,
+    pub created_at: u64,
+}
+
+/// A reference's slot name and the content hash of its original bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceSlot {
+    pub slot: String,
+    pub hash: String,
+}
+
+/// Generation settings recovered from another tool's PNG text chunks.
+#[derive(Debug, Default)]
+pub struct ForeignGeneration {
+    pub source: &'static str,
+    pub prompt: String,
+    pub negative_prompt: Option<String>,
+    pub seed: Option<u64>,
+    pub size: Option<String>,
+    pub model: Option<String>,
+}
+
+pub fn generator_name() -> String {
+    format!("ai-image-gen {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Writes `metadata` into the image container without touching pixel data:
+/// iTXt chunks for PNG and an XMP packet for JPEG and WebP.
+pub fn embed_metadata(data: Vec<u8>, metadata: &GenerationMetadata) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_string(metadata)
+        .map_err(|err| format!("Unable to serialise image metadata: {}", err))?;
+
+    match detect_image_format(&data)? {
+        image::ImageFormat::Png => embed_png(data, metadata, &json),
+        image::ImageFormat::Jpeg => embed_jpeg(data, metadata, &json),
+        image::ImageFormat::WebP => embed_webp(data, &build_xmp_packet(metadata, &json, true)),
+        other => Err(format!(
+            "Embedding metadata is not supported for '{}'.",
+            other.to_mime_type()
+        )),
+    }
+}
+
+/// Reads back metadata written by `embed_metadata`, if any.
+pub fn extract_metadata(data: &[u8]) -> Result<Option<GenerationMetadata>, String> {
+    let json = match detect_image_format(data)? {
+        image::ImageFormat::Png => read_png_text_chunks(data)?
+            .into_iter()
+            .find(|(keyword, _)| keyword == METADATA_KEYWORD)
+            .map(|(_, value)| value),
+        image::ImageFormat::Jpeg | image::ImageFormat::WebP => {
+            read_xmp_packet(data)?.and_then(|packet| read_xmp_generation(&packet))
+        }
+        _ => None,
+    };
+
+    json.map(|value| {
+        serde_json::from_str(&value)
+            .map_err(|err| format!("Unable to parse embedded metadata: {}", err))
+    })
+    .transpose()
+}
+
+/// Returns the embedded ICC colour profile of a PNG, JPEG or WebP image.
+pub fn read_icc_profile(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let image = DynImage::from_bytes(Bytes::copy_from_slice(data))
+        .map_err(|err| format!("Unable to read image container: {}", err))?;
+
+    Ok(image
+        .and_then(|image| image.icc_profile())
+        .map(|profile| profile.to_vec()))
+}
+
+/// Replaces (or with `None`, removes) the ICC colour profile of a PNG, JPEG or
+/// WebP image. Other formats are returned unchanged.
+pub fn write_icc_profile(data: Vec<u8>, profile: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let Some(mut image) = DynImage::from_bytes(Bytes::from(data.clone()))
+        .map_err(|err| format!("Unable to read image container: {}", err))?
+    else {
+        return Ok(data);
+    };
+
+    image.set_icc_profile(profile.map(Bytes::copy_from_slice));
+    Ok(image.encoder().bytes().to_vec())
+}
+
+/// Returns every uncompressed tEXt / iTXt entry of a PNG as keyword/value pairs.
+pub fn read_png_text_chunks(data: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let png = Png::from_bytes(Bytes::copy_from_slice(data))
+        .map_err(|err| format!("Unable to read PNG chunks: {}", err))?;
+
+    Ok(png
+        .chunks()
+        .iter()
+        .filter_map(|chunk| match chunk.kind() {
+            PNG_TEXT => parse_text_chunk(chunk.contents()),
+            PNG_ITXT => parse_itxt_chunk(chunk.contents()),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Recognises the AUTOMATIC1111 `parameters` chunk and ComfyUI's `prompt`
+/// graph among PNG text chunks.
+pub fn parse_foreign_generation(chunks: &[(String, String)]) -> Option<ForeignGeneration> {
+    let find = |keyword: &str| {
+        chunks
+            .iter()
+            .find(|(candidate, _)| candidate == keyword)
+            .map(|(_, value)| value.as_str())
+    };
+
+    if let Some(parameters) = find("parameters") {
+        return parse_a1111_parameters(parameters);
+    }
+
+    find("prompt").and_then(parse_comfyui_prompt)
+}
+
+/// Parses the A1111 layout: positive prompt lines, an optional
+/// `Negative prompt:` block and a final `Steps: .., Seed: .., Size: ..` line.
+fn parse_a1111_parameters(text: &str) -> Option<ForeignGeneration> {
+    let lines: Vec<&str> = text.lines().collect();
+    let settings_index = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("Steps:"));
+
+    let body = &lines[..settings_index.unwrap_or(lines.len())];
+    let negative_index = body
+        .iter()
+        .position(|line| line.trim_start().starts_with("Negative prompt:"));
+
+    let prompt = body[..negative_index.unwrap_or(body.len())]
+        .join("\n")
+        .trim()
+        .to_string();
+    let negative_prompt = negative_index
+        .map(|index| {
+            body[index..]
+                .join("\n")
+                .trim_start()
+                .trim_start_matches("Negative prompt:")
+                .trim()
+                .to_string()
+        })
+        .filter(|value| !value.is_empty());
+
+    let mut generation = ForeignGeneration {
+        source: "automatic1111",
+        prompt,
+        negative_prompt,
+        ..ForeignGeneration::default()
+    };
+
+    if let Some(settings) = settings_index.map(|index| lines[index]) {
+        for entry in settings.split(", ") {
+            let Some((key, value)) = entry.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "Seed" => generation.seed = value.parse().ok(),
+                "Size" => generation.size = Some(value.to_string()),
+                "Model" => generation.model = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    if generation.prompt.is_empty() {
+        return None;
+    }
+
+    Some(generation)
+}
+
+/// Walks a ComfyUI API-format graph from its sampler node back to the text
+/// encoders feeding it.
+fn parse_comfyui_prompt(json: &str) -> Option<ForeignGeneration> {
+    let graph: serde_json::Map<String, Value> = serde_json::from_str(json).ok()?;
+
+    let class_of = |node: &Value| node["class_type"].as_str().unwrap_or_default().to_string();
+    let linked_text = |link: &Value| {
+        let node_id = link.get(0)?.as_str()?;
+        graph.get(node_id)?["inputs"]["text"]
+            .as_str()
+            .map(str::to_string)
+    };
+
+    let mut generation = ForeignGeneration {
+        source: "comfyui",
+        ..ForeignGeneration::default()
+    };
+
+    if let Some(sampler) = graph
+        .values()
+        .find(|node| class_of(node).contains("KSampler"))
+    {
+        let inputs = &sampler["inputs"];
+        generation.prompt = linked_text(&inputs["positive"]).unwrap_or_default();
+        generation.negative_prompt =
+            linked_text(&inputs["negative"]).filter(|value| !value.trim().is_empty());
+        generation.seed = inputs["seed"]
+            .as_u64()
+            .or_else(|| inputs["noise_seed"].as_u64());
+    }
+
+    if generation.prompt.is_empty() {
+        generation.prompt = graph
+            .values()
+            .filter(|node| class_of(node).starts_with("CLIPTextEncode"))
+            .find_map(|node| node["inputs"]["text"].as_str().map(str::to_string))?;
+    }
+
+    for node in graph.values() {
+        let inputs = &node["inputs"];
+        match class_of(node).as_str() {
+            "EmptyLatentImage" => {
+                if let (Some(width), Some(height)) =
+                    (inputs["width"].as_u64(), inputs["height"].as_u64())
+                {
+                    generation.size = Some(format!("{width}x{height}"));
+                }
+            }
+            "CheckpointLoaderSimple" => {
+                generation.model = inputs["ckpt_name"].as_str().map(str::to_string);
+            }
+            _ => {}
+        }
+    }
+
+    Some(generation)
+}
+
+fn embed_png(data: Vec<u8>, metadata: &GenerationMetadata, json: &str) -> Result<Vec<u8>, String> {
+    let mut png = Png::from_bytes(Bytes::from(data))
+        .map_err(|err| format!("Unable to read PNG chunks: {}", err))?;
+
+    png.chunks_mut().retain(|chunk| {
+        let keyword = match chunk.kind() {
+            PNG_TEXT => parse_text_chunk(chunk.contents()),
+            PNG_ITXT => parse_itxt_chunk(chunk.contents()),
+            _ => None,
+        };
+        !matches!(
+            keyword,
+            Some((keyword, _)) if REPLACED_PNG_KEYWORDS.contains(&keyword.as_str())
+        )
+    });
+
+    let chunks = [
+        build_text_chunk(PNG_SOFTWARE_KEYWORD, &metadata.generator),
+        build_itxt_chunk(PNG_DESCRIPTION_KEYWORD, &metadata.prompt),
+        build_itxt_chunk(METADATA_KEYWORD, json),
+    ];
+
+    let end = png.chunks().len().saturating_sub(1);
+    for (offset, chunk) in chunks.into_iter().enumerate() {
+        png.chunks_mut().insert(end + offset, chunk);
+    }
+
+    Ok(png.encoder().bytes().to_vec())
+}
+
+/// The XMP packet has to fit in one APP1 segment, so when the prompt is too
+/// long it loses the readable description and keeps only the JSON; if even
+/// that doesn't fit, nothing is embedded.
+fn embed_jpeg(data: Vec<u8>, metadata: &GenerationMetadata, json: &str) -> Result<Vec<u8>, String> {
+    let fits = |packet: &str| XMP_JPEG_PREFIX.len() + packet.len() <= MAX_JPEG_SEGMENT_CONTENTS;
+    let mut packet = build_xmp_packet(metadata, json, true);
+    if !fits(&packet) {
+        packet = build_xmp_packet(metadata, json, false);
+    }
+    if !fits(&packet) {
+        return Err(format!(
+            "Image metadata is too large for a JPEG ({} bytes, at most {}).",
+            XMP_JPEG_PREFIX.len() + packet.len(),
+            MAX_JPEG_SEGMENT_CONTENTS
+        ));
+    }
+
+    let mut jpeg = Jpeg::from_bytes(Bytes::from(data))
+        .map_err(|err| format!("Unable to read JPEG segments: {}", err))?;
+
+    jpeg.segments_mut().retain(|segment| {
+        !(segment.marker() == markers::APP1 && segment.contents().starts_with(XMP_JPEG_PREFIX))
+    });
+
+    let mut contents = XMP_JPEG_PREFIX.to_vec();
+    contents.extend_from_slice(packet.as_bytes());
+    let segment = JpegSegment::new_with_contents(markers::APP1, Bytes::from(contents));
+
+    let position = jpeg
+        .segments()
+        .iter()
+        .position(|segment| !(markers::APP0..=markers::APP15).contains(&segment.marker()))
+        .unwrap_or(0);
+    jpeg.segments_mut().insert(position, segment);
+
+    Ok(jpeg.encoder().bytes().to_vec())
+}
+
+fn embed_webp(data: Vec<u8>, packet: &str) -> Result<Vec<u8>, String> {
+    let mut webp = WebP::from_bytes(Bytes::from(data))
+        .map_err(|err| format!("Unable to read WebP chunks: {}", err))?;
+
+    let (width, height) = webp
+        .dimensions()
+        .ok_or_else(|| "Unable to read WebP dimensions.".to_string())?;
+
+    webp.remove_chunks_by_id(CHUNK_XMP);
+    webp.chunks_mut().push(RiffChunk::new(
+        CHUNK_XMP,
+        RiffContent::Data(Bytes::copy_from_slice(packet.as_bytes())),
+    ));
+
+    // XMP is only allowed in the extended format, whose VP8X header carries a
+    // flag for every optional chunk and the canvas size.
+    let mut flags = 0b0000_0100u8;
+    if webp.has_chunk(CHUNK_ICCP) {
+        flags |= 0b0010_0000;
+    }
+    if webp.has_chunk(CHUNK_ALPH) || webp.has_chunk(CHUNK_VP8L) {
+        flags |= 0b0001_0000;
+    }
+    if webp.has_chunk(CHUNK_EXIF) {
+        flags |= 0b0000_1000;
+    }
+
+    match webp
+        .chunk_by_id(CHUNK_VP8X)
+        .and_then(|chunk| chunk.content().data())
+    {
+        Some(existing) => {
+            let mut header = existing.to_vec();
+            if let Some(first) = header.first_mut() {
+                *first |= flags;
+            }
+            let position = webp
+                .chunks()
+                .iter()
+                .position(|chunk| chunk.id() == CHUNK_VP8X)
+                .unwrap_or(0);
+            webp.chunks_mut()[position] =
+                RiffChunk::new(CHUNK_VP8X, RiffContent::Data(Bytes::from(header)));
+        }
+        None => {
+            let mut header = vec![flags, 0, 0, 0];
+            header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            webp.chunks_mut().insert(
+                0,
+                RiffChunk::new(CHUNK_VP8X, RiffContent::Data(Bytes::from(header))),
+            );
+        }
+    }
+
+    Ok(webp.encoder().bytes().to_vec())
+}
+
+fn read_xmp_packet(data: &[u8]) -> Result<Option<String>, String> {
+    let packet = match detect_image_format(data)? {
+        image::ImageFormat::Jpeg => Jpeg::from_bytes(Bytes::copy_from_slice(data))
+            .map_err(|err| format!("Unable to read JPEG segments: {}", err))?
+            .segments_by_marker(markers::APP1)
+            .find_map(|segment| {
+                segment
+                    .contents()
+                    .strip_prefix(XMP_JPEG_PREFIX)
+                    .map(|packet| String::from_utf8_lossy(packet).into_owned())
+            }),
+        image::ImageFormat::WebP => WebP::from_bytes(Bytes::copy_from_slice(data))
+            .map_err(|err| format!("Unable to read WebP chunks: {}", err))?
+            .chunk_by_id(CHUNK_XMP)
+            .and_then(|chunk| chunk.content().data())
+            .map(|packet| String::from_utf8_lossy(packet).into_owned()),
+        _ => None,
+    };
+
+    Ok(packet)
+}
+
+fn build_xmp_packet(metadata: &GenerationMetadata, json: &str, description: bool) -> String {
+    let description = if description {
+        format!(
+            "   <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n",
+            escape_xml(&metadata.prompt)
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\"\n",
+            "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+            "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+            "    xmlns:aig=\"{namespace}\">\n",
+            "   <xmp:CreatorTool>{generator}</xmp:CreatorTool>\n",
+            "{description}",
+            "   <aig:generation>{json}</aig:generation>\n",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        namespace = XMP_NAMESPACE,
+        generator = escape_xml(&metadata.generator),
+        description = description,
+        json = escape_xml(json),
+    )
+}
+
+fn read_xmp_generation(packet: &str) -> Option<String> {
+    let start = packet.find("<aig:generation>")? + "<aig:generation>".len();
+    let end = start + packet[start..].find("</aig:generation>")?;
+    Some(unescape_xml(&packet[start..end]))
+}
+
+fn build_text_chunk(keyword: &str, text: &str) -> PngChunk {
+    // tEXt is Latin-1 only; anything else is replaced rather than mis-encoded.
+    let mut contents: Vec<u8> = keyword.bytes().collect();
+    contents.push(0);
+    contents.extend(text.chars().map(|ch| u8::try_from(ch).unwrap_or(b'?')));
+    PngChunk::new(PNG_TEXT, Bytes::from(contents))
+}
+
+fn build_itxt_chunk(keyword: &str, text: &str) -> PngChunk {
+    let mut contents: Vec<u8> = keyword.bytes().collect();
+    // Null separator, compression flag, compression method, empty language
+    // tag and empty translated keyword.
+    contents.extend_from_slice(&[0, 0, 0, 0, 0]);
+    contents.extend_from_slice(text.as_bytes());
+    PngChunk::new(PNG_ITXT, Bytes::from(contents))
+}
+
+fn parse_text_chunk(contents: &[u8]) -> Option<(String, String)> {
+    let separator = contents.iter().position(|byte| *byte == 0)?;
+    let keyword = contents[..separator]
+        .iter()
+        .map(|byte| *byte as char)
+        .collect();
+    let text = contents[separator + 1..]
+        .iter()
+        .map(|byte| *byte as char)
+        .collect();
+    Some((keyword, text))
+}
+
+fn parse_itxt_chunk(contents: &[u8]) -> Option<(String, String)> {
+    let separator = contents.iter().position(|byte| *byte == 0)?;
+    let keyword = String::from_utf8_lossy(&contents[..separator]).into_owned();
+
+    let rest = contents.get(separator + 1..)?;
+    let (compressed, rest) = rest.split_first()?;
+    if *compressed != 0 {
+        return None;
+    }
+
+    // Skip the compression method, language tag and translated keyword.
+    let rest = rest.get(1..)?;
+    let language_end = rest.iter().position(|byte| *byte == 0)?;
+    let rest = rest.get(language_end + 1..)?;
+    let translated_end = rest.iter().position(|byte| *byte == 0)?;
+    let text = rest.get(translated_end + 1..)?;
+
+    Some((keyword, String::from_utf8_lossy(text).into_owned()))
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(prompt: String) -> GenerationMetadata {
+        GenerationMetadata {
+            generator: generator_name(),
+            prompt,
+            system_prompt: None,
+            model: "test-model".to_string(),
+            parameters: GenerationParameters::default(),
+            seed: None,
+            reference_hashes: Vec::new(),
+            reference_slots: Vec::new(),
+            created_at: 0,
+        }
+    }
+
+    fn jpeg() -> Vec<u8> {
+        let image = image::DynamicImage::new_rgb8(4, 4);
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut bytes, image::ImageFormat::Jpeg)
+            .expect("encode test JPEG");
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn long_jpeg_prompt_drops_the_description() {
+        let prompt = "a".repeat(40 * 1024);
+        let embedded = embed_metadata(jpeg(), &metadata(prompt.clone())).expect("embed");
+
+        let packet = read_xmp_packet(&embedded).unwrap().expect("XMP packet");
+        assert!(!packet.contains("<dc:description>"));
+        let restored = extract_metadata(&embedded).unwrap().expect("metadata");
+        assert_eq!(restored.prompt, prompt);
+    }
+
+    #[test]
+    fn oversized_jpeg_metadata_is_an_error() {
+        let prompt = "a".repeat(70 * 1024);
+        assert!(embed_metadata(jpeg(), &metadata(prompt)).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use std::io::Cursor;
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use llmapi::types::LLMProvider;
+
+use crate::constants::MAX_OUTPUT_DIMENSION;
+use crate::models::{ResizeMode, ResizeOptions};
+
+const JPEG_QUALITY: u8 = 90;
+const AVIF_SPEED: u8 = 6;
+const MIN_BUDGET_DIMENSION: u32 = 256;
+
+pub struct NormalizedImage {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+impl NormalizedImage {
+    pub fn mime_type(&self) -> &'static str {
+        self.format.to_mime_type()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.format
+            .extensions_str()
+            .first()
+            .copied()
+            .unwrap_or("bin")
+    }
+}
+
+pub struct ImageBudget {
+    pub max_dimension: u32,
+    pub max_bytes: usize,
+    pub preferred_format: ImageFormat,
+}
+
+/// Size limits applied to reference images before they are sent inline.
+pub fn reference_image_budget(provider: LLMProvider) -> ImageBudget {
+    match provider {
+        LLMProvider::Gemini => ImageBudget {
+            max_dimension: 2048,
+            max_bytes: 3 * 1024 * 1024,
+            preferred_format: ImageFormat::Jpeg,
+        },
+        LLMProvider::OpenAI => ImageBudget {
+            max_dimension: 2048,
+            max_bytes: 4 * 1024 * 1024,
+            preferred_format: ImageFormat::Jpeg,
+        },
+        LLMProvider::Anthropic => ImageBudget {
+            max_dimension: 1568,
+            max_bytes: 3 * 1024 * 1024,
+            preferred_format: ImageFormat::Jpeg,
+        },
+    }
+}
+
+pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat, String> {
+    let format = image::guess_format(data)
+        .map_err(|_| "Data is not a recognised image format.".to_string())?;
+
+    if !format.reading_enabled() {
+        return Err(format!(
+            "Image format '{}' is not supported.",
+            format.to_mime_type()
+        ));
+    }
+
+    Ok(format)
+}
+
+pub fn parse_output_format(value: Option<&str>) -> Result<Option<ImageFormat>, String> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+
+    match value.to_lowercase().as_str() {
+        "original" => Ok(None),
+        "png" | "image/png" => Ok(Some(ImageFormat::Png)),
+        "webp" | "image/webp" => Ok(Some(ImageFormat::WebP)),
+        other => Err(format!("Unsupported output format '{}'.", other)),
+    }
+}
+
+/// Width and height from the image header, without decoding pixels.
+pub fn image_dimensions(data: &[u8]) -> Result<(u32, u32), String> {
+    let format = detect_image_format(data)?;
+    ImageReader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .map_err(|err| format!("Failed to read image dimensions: {}", err))
+}
+
+/// Decodes `data` with its EXIF orientation applied.
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage, String> {
+    let (mut image, _, orientation) = read_image(data)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn read_image(data: &[u8]) -> Result<(DynamicImage, ImageFormat, Orientation), String> {
+    let format = detect_image_format(data)?;
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format)
+        .into_decoder()
+        .map_err(|err| format!("Failed to read image: {}", err))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+
+    let image = DynamicImage::from_decoder(decoder)
+        .map_err(|err| format!("Failed to decode image: {}", err))?;
+
+    Ok((image, format, orientation))
+}
+
+/// Decodes `data`, bakes in its EXIF orientation and shrinks it to fit within
+/// `max_dimension`. The original bytes are kept when nothing had to change.
+pub fn normalize_image(
+    data: &[u8],
+    max_dimension: u32,
+    output_format: Option<ImageFormat>,
+) -> Result<NormalizedImage, String> {
+    let (mut image, format, orientation) = read_image(data)?;
+
+    let mut modified = orientation != Orientation::NoTransforms;
+    image.apply_orientation(orientation);
+
+    if max_dimension > 0 && image.width().max(image.height()) > max_dimension {
+        image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+        modified = true;
+    }
+
+    let target_format = output_format.unwrap_or(format);
+    if !modified && target_format == format {
+        return Ok(NormalizedImage {
+            bytes: data.to_vec(),
+            format,
+        });
+    }
+
+    let target_format = if target_format.writing_enabled() {
+        target_format
+    } else {
+        ImageFormat::Png
+    };
+
+    Ok(NormalizedImage {
+        bytes: encode_image(&image, target_format)?,
+        format: target_format,
+    })
+}
+
+/// Shrinks and re-encodes `data` until it fits within `budget`. Images that
+/// already fit in a format every provider accepts are passed through as-is.
+pub fn fit_image_to_budget(data: &[u8], budget: &ImageBudget) -> Result<NormalizedImage, String> {
+    let (mut image, format, orientation) = read_image(data)?;
+
+    let mut modified = orientation != Orientation::NoTransforms;
+    image.apply_orientation(orientation);
+
+    if image.width().max(image.height()) > budget.max_dimension {
+        image = image.resize(
+            budget.max_dimension,
+            budget.max_dimension,
+            FilterType::Lanczos3,
+        );
+        modified = true;
+    }
+
+    let accepted_format = matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+    );
+    if !modified && accepted_format && data.len() <= budget.max_bytes {
+        return Ok(NormalizedImage {
+            bytes: data.to_vec(),
+            format,
+        });
+    }
+
+    let target_format = if budget.preferred_format == ImageFormat::Jpeg && image.color().has_alpha()
+    {
+        ImageFormat::Png
+    } else {
+        budget.preferred_format
+    };
+
+    let mut bytes = encode_image(&image, target_format)?;
+    while bytes.len() > budget.max_bytes {
+        let longest = image.width().max(image.height());
+        if longest <= MIN_BUDGET_DIMENSION {
+            return Err(format!(
+                "Image cannot be reduced below {} bytes.",
+                budget.max_bytes
+            ));
+        }
+
+        let next = (longest * 3 / 4).max(MIN_BUDGET_DIMENSION);
+        image = image.resize(next, next, FilterType::Lanczos3);
+        bytes = encode_image(&image, target_format)?;
+    }
+
+    Ok(NormalizedImage {
+        bytes,
+        format: target_format,
+    })
+}
+
+pub fn encode_image(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut buffer = Cursor::new(Vec::new());
+
+    let result = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY)),
+        ImageFormat::WebP => {
+            DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut buffer, format)
+        }
+        _ => image.write_to(&mut buffer, format),
+    };
+    result.map_err(|err| format!("Failed to encode image: {}", err))?;
+
+    Ok(buffer.into_inner())
+}
+
+/// Resizes to `options`: `fit` keeps the aspect ratio inside the box, `fill`
+/// covers the box and crops the overflow, `exact` stretches to it.
+pub fn resize_image(image: &DynamicImage, options: &ResizeOptions) -> Result<DynamicImage, String> {
+    if options.width == 0 || options.height == 0 {
+        return Err("Resize width and height must be greater than zero.".into());
+    }
+    if options.width.max(options.height) > MAX_OUTPUT_DIMENSION {
+        return Err(format!(
+            "Resize width and height must be at most {} px, got {}x{}.",
+            MAX_OUTPUT_DIMENSION, options.width, options.height
+        ));
+    }
+
+    let resized = match options.mode {
+        ResizeMode::Fit => image.resize(options.width, options.height, FilterType::Lanczos3),
+        ResizeMode::Fill => {
+            image.resize_to_fill(options.width, options.height, FilterType::Lanczos3)
+        }
+        ResizeMode::Exact => {
+            image.resize_exact(options.width, options.height, FilterType::Lanczos3)
+        }
+    };
+
+    Ok(resized)
+}
+
+/// Encodes with an explicit quality (1-100). PNG ignores the quality, WebP is
+/// lossless unless `lossless` is false, and AVIF treats 100 as its best setting.
+pub fn encode_image_with_quality(
+    image: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+    lossless: bool,
+) -> Result<Vec<u8>, String> {
+    let quality = quality.clamp(1, 100);
+    let mut buffer = Cursor::new(Vec::new());
+
+    let result = match format {
+        ImageFormat::Png => image.write_to(&mut buffer, format),
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality)),
+        ImageFormat::WebP if lossless => {
+            DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut buffer, format)
+        }
+        ImageFormat::WebP => {
+            // The `image` WebP encoder is lossless only.
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode(f32::from(quality));
+            return Ok(encoded.to_vec());
+        }
+        ImageFormat::Avif => DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(
+            AvifEncoder::new_with_speed_quality(&mut buffer, AVIF_SPEED, quality),
+        ),
+        other => {
+            return Err(format!(
+                "Encoding '{}' is not supported.",
+                other.to_mime_type()
+            ))
+        }
+    };
+    result.map_err(|err| format!("Failed to encode image: {}", err))?;
+
+    Ok(buffer.into_inner())
+}
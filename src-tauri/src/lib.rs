@@ -1,25 +1,77 @@
+mod annotate;
+mod background;
 mod commands;
 mod constants;
 mod fs_utils;
+mod image_metadata;
+mod image_utils;
+mod lineage;
+mod llm;
+mod mask;
+mod migrations;
 mod models;
+mod naming;
+mod onnx;
+mod post_process;
+mod store;
+mod templates;
 
+pub use commands::background::remove_background;
+pub use commands::captions::{caption_images, get_image_captions};
+pub use commands::edit::edit_image;
+pub use commands::enhance::enhance_prompt;
+pub use commands::export::export_images;
 pub use commands::generate::{generate_image, list_generation_logs};
 pub use commands::library::{
     delete_images, delete_output_images, get_output_dir_path, list_images, list_output_images,
     open_dir, upload_images,
 };
+pub use commands::lineage::{branch_from_image, get_image_lineage};
+pub use commands::metadata::{import_image_metadata, read_image_metadata};
+pub use commands::preflight::preflight_generation;
+pub use commands::prompt_bundles::{export_prompt_templates, import_prompt_templates};
+pub use commands::prompt_search::search_prompt_templates;
+pub use commands::prompt_versions::{
+    diff_prompt_versions, list_prompt_versions, restore_prompt_version,
+};
 pub use commands::prompts::{
-    list_prompt_templates, load_prompts, remove_prompts_by_id, save_prompts,
+    get_default_prompts, list_prompt_templates, load_prompts, remove_prompts_by_id, render_prompt,
+    save_prompts, set_default_prompts,
+};
+pub use commands::reference::prepare_reference_image;
+
+pub use commands::sessions::{
+    continue_edit_session, delete_edit_session, get_edit_session, list_edit_sessions,
+    start_edit_session,
 };
 
+pub use commands::upscale::upscale_image;
+pub use commands::variations::generate_variations;
+
 pub use constants::{
-    DEFAULT_GEMINI_ENDPOINT, DEFAULT_IMAGE_MIME, DEFAULT_IMAGE_MODEL, INPUT_DIR_NAME,
-    OUTPUT_DIR_NAME, PROMPTS_DIR_NAME, SYSTEM_PROMPT_FILE, USER_PROMPT_FILE,
+    CACHE_DIR_NAME, DEFAULT_ANTHROPIC_CHAT_MODEL, DEFAULT_ANTHROPIC_ENDPOINT,
+    DEFAULT_GEMINI_CHAT_MODEL, DEFAULT_GEMINI_ENDPOINT, DEFAULT_IMAGE_MIME, DEFAULT_IMAGE_MODEL,
+    DEFAULT_MAX_UPLOAD_DIMENSION, DEFAULT_OPENAI_CHAT_MODEL, DEFAULT_OPENAI_EDIT_MODEL,
+    DEFAULT_OPENAI_ENDPOINT, GEMINI_FILE_CACHE_FILE, GEMINI_FILE_UPLOAD_THRESHOLD, INPUT_DIR_NAME,
+    MAX_OUTPUT_DIMENSION, OUTPUT_DIR_NAME, PROMPTS_DIR_NAME, SESSIONS_DIR_NAME, SYSTEM_PROMPT_FILE,
+    USER_PROMPT_FILE,
 };
 
 pub use models::{
-    GenerateImageRequest, GeneratedImage, GeneratedImageResponsePayload, ReferenceImagePayload,
-    SavePromptsPayload, StoredImage, UploadImagePayload,
+    Annotation, AspectMode, CaptionImagesRequest, CaptionImagesResponse, CaptionedImage,
+    ColorProfileMode, ContinueEditSessionRequest, CropRect, DefaultPrompts, EditImageRequest,
+    EnhancePromptRequest, EnhancePromptResponse, EnhancedPrompt, ExportImagesPayload,
+    ExportImagesResult, ExportPromptTemplatesPayload, ExportPromptTemplatesResult, ExportedImage,
+    FocalPoint, GenerateImageRequest, GenerateVariationsRequest, GenerateVariationsResponse,
+    GeneratedImage, GeneratedImageResponsePayload, ImageCaption, ImportConflictStrategy,
+    ImportImageMetadataPayload, ImportPromptTemplatesPayload, ImportPromptTemplatesReport,
+    ImportedGeneration, MaskMode, OutputNamingOptions, PixelPoint, PostProcessOptions,
+    PreflightCheck, PreflightIssue, PreflightReport, PreflightSeverity, PrepareReferenceRequest,
+    PromptBundleFormat, PromptSearchQuery, PromptTemplateRef, ReferenceImagePayload,
+    RemoveBackgroundRequest, RemoveBackgroundResponse, RenderedPrompt, ResampleFilter, ResizeMode,
+    ResizeOptions, SavePromptsPayload, SetDefaultPromptsPayload, StartEditSessionRequest,
+    StoredImage, TemplateImportAction, TemplateImportChange, TemplateVariable,
+    TemplateVariableKind, UploadImagePayload, UpscaleImageRequest,
 };
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -39,7 +91,35 @@ pub fn run() {
             load_prompts,
             save_prompts,
             remove_prompts_by_id,
-            list_generation_logs
+            list_generation_logs,
+            read_image_metadata,
+            import_image_metadata,
+            export_images,
+            edit_image,
+            start_edit_session,
+            continue_edit_session,
+            list_edit_sessions,
+            get_edit_session,
+            delete_edit_session,
+            get_image_lineage,
+            branch_from_image,
+            generate_variations,
+            upscale_image,
+            remove_background,
+            prepare_reference_image,
+            render_prompt,
+            list_prompt_versions,
+            diff_prompt_versions,
+            restore_prompt_version,
+            export_prompt_templates,
+            import_prompt_templates,
+            search_prompt_templates,
+            get_default_prompts,
+            set_default_prompts,
+            enhance_prompt,
+            caption_images,
+            get_image_captions,
+            preflight_generation
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
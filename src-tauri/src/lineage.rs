@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fs_utils::ensure_output_dir;
+use crate::store;
+
+const LINEAGE_FILE_NAME: &str = "lineage.json";
+
+/// How an output image was produced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationOperation {
+    #[default]
+    Generate,
+    Edit,
+    Refine,
+    Variation,
+    Upscale,
+    RemoveBackground,
+}
+
+/// One output in the version tree. Unlike the generation log this store is
+/// never trimmed, so ancestry stays intact for old images.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineageNode {
+    pub image: String,
+    pub operation: GenerationOperation,
+    #[serde(default)]
+    pub parents: Vec<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// Slot of each entry in `references`, by position; empty for nodes
+    /// recorded before slots were.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference_slots: Vec<String>,
+    pub prompt: String,
+    pub system_prompt: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineageTree {
+    pub node: LineageNode,
+    pub children: Vec<LineageTree>,
+}
+
+pub async fn load_lineage() -> Result<Vec<LineageNode>, String> {
+    store::load(&lineage_path().await?).await
+}
+
+pub async fn record_lineage(node: LineageNode) -> Result<(), String> {
+    let mut nodes = store::open::<Vec<LineageNode>>(&lineage_path().await?).await?;
+    nodes.retain(|existing| existing.image != node.image);
+    nodes.push(node);
+    nodes.save().await
+}
+
+async fn lineage_path() -> Result<PathBuf, String> {
+    Ok(ensure_output_dir().await?.join(LINEAGE_FILE_NAME))
+}
+
+/// Every ancestor of `image`, nearest first.
+pub fn ancestors_of(nodes: &[LineageNode], image: &str) -> Vec<LineageNode> {
+    let by_image: HashMap<&str, &LineageNode> = nodes
+        .iter()
+        .map(|node| (node.image.as_str(), node))
+        .collect();
+
+    let mut seen = HashSet::from([image.to_string()]);
+    let mut queue: VecDeque<&str> = by_image
+        .get(image)
+        .map(|node| node.parents.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    let mut ancestors = Vec::new();
+
+    while let Some(parent) = queue.pop_front() {
+        if !seen.insert(parent.to_string()) {
+            continue;
+        }
+        if let Some(node) = by_image.get(parent) {
+            ancestors.push((*node).clone());
+            queue.extend(node.parents.iter().map(String::as_str));
+        }
+    }
+
+    ancestors
+}
+
+/// Builds the tree of images derived from `node`.
+pub fn descendants_of(nodes: &[LineageNode], node: LineageNode) -> LineageTree {
+    let mut seen = HashSet::new();
+    build_tree(nodes, node, &mut seen)
+}
+
+fn build_tree(nodes: &[LineageNode], node: LineageNode, seen: &mut HashSet<String>) -> LineageTree {
+    seen.insert(node.image.clone());
+
+    let mut children = Vec::new();
+    for child in nodes
+        .iter()
+        .filter(|candidate| candidate.parents.contains(&node.image))
+    {
+        if !seen.contains(&child.image) {
+            children.push(build_tree(nodes, child.clone(), seen));
+        }
+    }
+
+    LineageTree { node, children }
+}
@@ -0,0 +1,135 @@
+use base64::engine::general_purpose::STANDARD as BASE64_ENGINE;
+use base64::Engine;
+use llmapi::providers::get_llm_chat;
+use llmapi::types::{LLMClient, LLMMessage, LLMMessageType, LLMProvider, LLMType, LLMUserType};
+
+use crate::constants::{
+    DEFAULT_ANTHROPIC_CHAT_MODEL, DEFAULT_ANTHROPIC_ENDPOINT, DEFAULT_GEMINI_CHAT_MODEL,
+    DEFAULT_GEMINI_ENDPOINT, DEFAULT_OPENAI_CHAT_MODEL, DEFAULT_OPENAI_ENDPOINT,
+};
+use crate::image_utils::{fit_image_to_budget, reference_image_budget};
+
+/// Parses a provider name from the frontend, defaulting to Gemini.
+pub fn parse_provider(value: Option<&str>) -> Result<LLMProvider, String> {
+    match value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        None | Some("gemini") | Some("google") => Ok(LLMProvider::Gemini),
+        Some("openai") => Ok(LLMProvider::OpenAI),
+        Some("anthropic") | Some("claude") => Ok(LLMProvider::Anthropic),
+        Some(other) => Err(format!("Unknown provider '{}'.", other)),
+    }
+}
+
+pub fn provider_endpoint(provider: LLMProvider) -> &'static str {
+    match provider {
+        LLMProvider::Gemini => DEFAULT_GEMINI_ENDPOINT,
+        LLMProvider::OpenAI => DEFAULT_OPENAI_ENDPOINT,
+        LLMProvider::Anthropic => DEFAULT_ANTHROPIC_ENDPOINT,
+    }
+}
+
+/// Text model used for prompt assistance when the request names none.
+pub fn default_chat_model(provider: LLMProvider) -> &'static str {
+    match provider {
+        LLMProvider::Gemini => DEFAULT_GEMINI_CHAT_MODEL,
+        LLMProvider::OpenAI => DEFAULT_OPENAI_CHAT_MODEL,
+        LLMProvider::Anthropic => DEFAULT_ANTHROPIC_CHAT_MODEL,
+    }
+}
+
+pub fn require_api_key(value: Option<&str>) -> Result<&str, String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| "An API key is required.".to_string())
+}
+
+/// Picks the requested model or `default`, dropping Gemini's `models/` prefix.
+pub fn resolve_model(value: Option<&str>, default: &str) -> String {
+    let model = value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or(default);
+
+    model.strip_prefix("models/").unwrap_or(model).to_string()
+}
+
+/// Sends one instruction and one user turn to a chat model and returns the
+/// reply text. `max_tokens` overrides the provider's default reply limit.
+/// llmapi reports failures as a system message, which becomes the error here.
+pub async fn send_chat(
+    provider: LLMProvider,
+    api_key: &str,
+    model: &str,
+    instruction: String,
+    user_content: Vec<LLMMessageType>,
+    max_tokens: Option<u32>,
+) -> Result<String, String> {
+    // Gemini has no system role in `contents`, so the instruction goes first
+    // as a user turn there, as generation does with system prompts.
+    let instruction_role = match provider {
+        LLMProvider::Gemini => "Human",
+        LLMProvider::OpenAI | LLMProvider::Anthropic => "System",
+    };
+    let messages = vec![
+        LLMMessage::new(
+            None,
+            instruction_role,
+            vec![LLMMessageType::text(instruction)],
+        ),
+        LLMMessage::new(None, "Human", user_content),
+    ];
+
+    let mut client = LLMClient::new(
+        provider,
+        api_key,
+        provider_endpoint(provider),
+        model,
+        LLMType::Chat,
+    );
+    if let Some(max_tokens) = max_tokens {
+        client = client.with_max_tokens(max_tokens);
+    }
+    let reply = get_llm_chat(client).await(messages).await;
+    let text = reply
+        .content
+        .iter()
+        .filter_map(|part| match part {
+            LLMMessageType::TEXT(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if matches!(reply.role, LLMUserType::System) {
+        return Err(text);
+    }
+    Ok(text)
+}
+
+/// An image part for a chat message, resized to the provider's reference
+/// budget. `name` only serves to carry the MIME type through its extension.
+pub fn chat_image_part(
+    bytes: &[u8],
+    name: &str,
+    provider: LLMProvider,
+) -> Result<LLMMessageType, String> {
+    let prepared = fit_image_to_budget(bytes, &reference_image_budget(provider))?;
+    Ok(LLMMessageType::IMAGE {
+        data_b64: BASE64_ENGINE.encode(&prepared.bytes),
+        file_path: Some(format!("{name}.{}", prepared.extension())),
+    })
+}
+
+/// The outermost `{...}` in a model reply, skipping any Markdown code fence
+/// or commentary around it.
+pub fn extract_json_object(text: &str) -> Option<&str> {
+    match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => Some(&text[start..=end]),
+        _ => None,
+    }
+}
@@ -0,0 +1,274 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+
+use crate::models::MaskMode;
+
+const MASK_THRESHOLD: u8 = 128;
+
+/// Region to repaint in an edit request: 255 marks pixels to change.
+pub struct EditMask {
+    pub mask: GrayImage,
+}
+
+impl EditMask {
+    /// Builds the mask from `mask`, read according to `mode`, or from the
+    /// transparent pixels of `base` when no mask is given.
+    pub fn from_images(
+        base: &DynamicImage,
+        mask: Option<&DynamicImage>,
+        mode: MaskMode,
+    ) -> Result<Self, String> {
+        let (source, mode) = match mask {
+            Some(mask) => (mask, mode),
+            None => (base, MaskMode::Alpha),
+        };
+        let rgba = source.to_rgba8();
+
+        let has_transparency = rgba.pixels().any(|pixel| pixel[3] < 255);
+        if mask.is_none() && !has_transparency {
+            return Err(
+                "No mask supplied and the base image has no transparent area to edit.".into(),
+            );
+        }
+
+        let mut grey = GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let Rgba([red, green, blue, alpha]) = *rgba.get_pixel(x, y);
+            let selected = match mode {
+                MaskMode::Alpha => alpha < MASK_THRESHOLD,
+                MaskMode::Painted if has_transparency => alpha >= MASK_THRESHOLD,
+                MaskMode::Painted => {
+                    let luma =
+                        (u32::from(red) * 299 + u32::from(green) * 587 + u32::from(blue) * 114)
+                            / 1000;
+                    luma >= u32::from(MASK_THRESHOLD)
+                }
+            };
+            Luma([if selected { 255 } else { 0 }])
+        });
+
+        if grey.dimensions() != (base.width(), base.height()) {
+            grey = image::imageops::resize(&grey, base.width(), base.height(), FilterType::Nearest);
+        }
+
+        if grey.pixels().all(|pixel| pixel[0] == 0) {
+            return Err("Mask does not select any area to edit.".into());
+        }
+
+        Ok(Self { mask: grey })
+    }
+
+    /// Scales the mask to `width`x`height`, e.g. to match a base image that
+    /// was shrunk before sending. Any pixel touching the edit area stays
+    /// selected, so small areas survive the downscale.
+    pub fn resized(&self, width: u32, height: u32) -> Self {
+        if self.mask.dimensions() == (width, height) {
+            return Self {
+                mask: self.mask.clone(),
+            };
+        }
+
+        let mut mask = image::imageops::resize(&self.mask, width, height, FilterType::Triangle);
+        for pixel in mask.pixels_mut() {
+            pixel[0] = if pixel[0] > 0 { 255 } else { 0 };
+        }
+        Self { mask }
+    }
+
+    /// Smallest rectangle `(x, y, width, height)` covering the edit area.
+    pub fn bounds(&self) -> (u32, u32, u32, u32) {
+        let (mut min_x, mut min_y) = (u32::MAX, u32::MAX);
+        let (mut max_x, mut max_y) = (0, 0);
+
+        for (x, y, pixel) in self.mask.enumerate_pixels() {
+            if pixel[0] > 0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+
+        (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+    }
+
+    /// OpenAI-style mask: opaque everywhere except the transparent edit area.
+    pub fn to_alpha_mask(&self) -> DynamicImage {
+        let rgba = RgbaImage::from_fn(self.mask.width(), self.mask.height(), |x, y| {
+            let alpha = 255 - self.mask.get_pixel(x, y)[0];
+            Rgba([0, 0, 0, alpha])
+        });
+        DynamicImage::ImageRgba8(rgba)
+    }
+
+    /// Black and white mask (white = edit) for providers that take it as a
+    /// plain reference image.
+    pub fn to_reference_image(&self) -> DynamicImage {
+        DynamicImage::ImageLuma8(self.mask.clone())
+    }
+
+    /// Plain-language location of the edit area, for providers without
+    /// native mask support.
+    pub fn describe(&self) -> String {
+        let (width, height) = self.mask.dimensions();
+        let (x, y, region_width, region_height) = self.bounds();
+        let percent = |value: u32, total: u32| value * 100 / total.max(1);
+
+        format!(
+            "Only change the area from {}% to {}% of the width (left to right) and {}% to {}% of \
+             the height (top to bottom), i.e. pixels x {}-{}, y {}-{} of a {}x{} image. Keep \
+             everything outside that area unchanged.",
+            percent(x, width),
+            percent(x + region_width, width),
+            percent(y, height),
+            percent(y + region_height, height),
+            x,
+            x + region_width - 1,
+            y,
+            y + region_height - 1,
+            width,
+            height
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque black image with `painted` pixels set to `color`.
+    fn image_with(
+        width: u32,
+        height: u32,
+        painted: &[(u32, u32)],
+        color: Rgba<u8>,
+    ) -> DynamicImage {
+        let mut rgba = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+        for &(x, y) in painted {
+            rgba.put_pixel(x, y, color);
+        }
+        DynamicImage::ImageRgba8(rgba)
+    }
+
+    fn selected(mask: &EditMask) -> Vec<(u32, u32)> {
+        mask.mask
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel[0] == 255)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn transparent_base_pixels_are_the_edit_area_without_a_mask() {
+        let base = image_with(4, 4, &[(1, 2)], Rgba([255, 255, 255, 0]));
+        let mask = EditMask::from_images(&base, None, MaskMode::Painted).expect("mask");
+        assert_eq!(selected(&mask), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn opaque_base_without_a_mask_is_an_error() {
+        let base = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        assert!(EditMask::from_images(&base, None, MaskMode::Alpha).is_err());
+    }
+
+    #[test]
+    fn opaque_painted_mask_selects_bright_pixels() {
+        let base = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        let painted = image_with(4, 4, &[(0, 0), (3, 3)], Rgba([255, 255, 255, 255]));
+        let mut painted = painted.to_rgba8();
+        // Luma just under the threshold stays unselected.
+        painted.put_pixel(2, 2, Rgba([127, 127, 127, 255]));
+        let painted = DynamicImage::ImageRgba8(painted);
+
+        let mask = EditMask::from_images(&base, Some(&painted), MaskMode::Painted).expect("mask");
+        assert_eq!(selected(&mask), vec![(0, 0), (3, 3)]);
+    }
+
+    #[test]
+    fn transparent_painted_mask_selects_the_strokes() {
+        let base = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        let mut strokes = RgbaImage::new(4, 4);
+        strokes.put_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        let strokes = DynamicImage::ImageRgba8(strokes);
+
+        let mask = EditMask::from_images(&base, Some(&strokes), MaskMode::Painted).expect("mask");
+        assert_eq!(selected(&mask), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn alpha_mask_selects_transparent_pixels() {
+        let base = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        let alpha = image_with(4, 4, &[(2, 1)], Rgba([255, 255, 255, 0]));
+
+        let mask = EditMask::from_images(&base, Some(&alpha), MaskMode::Alpha).expect("mask");
+        assert_eq!(selected(&mask), vec![(2, 1)]);
+    }
+
+    #[test]
+    fn empty_mask_is_an_error() {
+        let base = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        let blank = image_with(4, 4, &[], Rgba([0, 0, 0, 255]));
+        assert!(EditMask::from_images(&base, Some(&blank), MaskMode::Painted).is_err());
+    }
+
+    #[test]
+    fn mask_is_scaled_to_the_base_image() {
+        let base = image_with(8, 8, &[], Rgba([0, 0, 0, 255]));
+        let painted = image_with(4, 4, &[(0, 0)], Rgba([255, 255, 255, 255]));
+
+        let mask = EditMask::from_images(&base, Some(&painted), MaskMode::Painted).expect("mask");
+        assert_eq!(mask.mask.dimensions(), (8, 8));
+        assert_eq!(selected(&mask), vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn downscaling_keeps_a_single_pixel_selection() {
+        let mut grey = GrayImage::new(100, 100);
+        grey.put_pixel(57, 31, Luma([255]));
+        let mask = EditMask { mask: grey }.resized(10, 10);
+
+        assert_eq!(mask.mask.dimensions(), (10, 10));
+        assert!(mask
+            .mask
+            .pixels()
+            .all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+        let area = selected(&mask);
+        assert!(!area.is_empty());
+        assert!(area
+            .iter()
+            .all(|&(x, y)| (4..=6).contains(&x) && (2..=4).contains(&y)));
+    }
+
+    #[test]
+    fn describe_reports_percentages_and_pixels() {
+        let mut grey = GrayImage::new(100, 50);
+        for y in 0..25 {
+            for x in 25..50 {
+                grey.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let mask = EditMask { mask: grey };
+
+        assert_eq!(mask.bounds(), (25, 0, 25, 25));
+        let description = mask.describe();
+        assert!(description.contains("from 25% to 50% of the width"));
+        assert!(description.contains("0% to 50% of the height"));
+        assert!(description.contains("pixels x 25-49, y 0-24 of a 100x50 image"));
+    }
+
+    #[test]
+    fn description_follows_the_resized_mask() {
+        let mut grey = GrayImage::new(200, 100);
+        for y in 50..100 {
+            for x in 100..200 {
+                grey.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let mask = EditMask { mask: grey }.resized(100, 50);
+
+        // The edit area may grow by a pixel, never shrink.
+        let (x, y, width, height) = mask.bounds();
+        assert!((49..=50).contains(&x) && (24..=25).contains(&y));
+        assert_eq!((x + width, y + height), (100, 50));
+        assert!(mask.describe().contains("of a 100x50 image"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Schema versions of the documents kept through `store`, and the steps that
+//! upgrade files written by older builds. To change a document's format, bump
+//! its `SCHEMA_VERSION` and append a migration from the previous version.
+
+use llmapi::providers::gemini::FileUriCache;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::commands::generate::GenerationLogEntry;
+use crate::commands::prompt_versions::PromptTemplateVersion;
+use crate::commands::prompts::PromptTemplates;
+use crate::commands::sessions::EditSession;
+use crate::lineage::LineageNode;
+use crate::models::ImageCaption;
+
+/// Rewrites a document's data from one schema version to the next.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Version written with every save. Files saved before documents were
+    /// versioned count as version 1; version 2 is the first in the envelope.
+    const SCHEMA_VERSION: u32;
+    /// `MIGRATIONS[i]` upgrades version `i + 1` to version `i + 2`.
+    const MIGRATIONS: &'static [Migration] = &[];
+}
+
+impl Versioned for Vec<PromptTemplates> {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for Vec<PromptTemplateVersion> {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for Vec<GenerationLogEntry> {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for Vec<LineageNode> {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for EditSession {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for ImageCaption {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+impl Versioned for FileUriCache {
+    const SCHEMA_VERSION: u32 = 2;
+    const MIGRATIONS: &'static [Migration] = &[adopt_envelope];
+}
+
+/// 1 → 2: the data is unchanged and only gains the envelope, but going
+/// through a migration means the bare original is backed up first.
+fn adopt_envelope(data: Value) -> Result<Value, String> {
+    Ok(data)
+}
+
+/// Upgrades `data` written at schema `version` to `T::SCHEMA_VERSION`.
+pub fn migrate<T: Versioned>(mut data: Value, version: u32) -> Result<Value, String> {
+    for from in version..T::SCHEMA_VERSION {
+        let migration = from
+            .checked_sub(1)
+            .and_then(|index| T::MIGRATIONS.get(index as usize))
+            .ok_or_else(|| format!("No migration from schema version {}.", from))?;
+        data = migration(data).map_err(|err| {
+            format!(
+                "Migration from schema version {} to {} failed: {}",
+                from,
+                from + 1,
+                err
+            )
+        })?;
+    }
+    Ok(data)
+}
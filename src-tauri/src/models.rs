@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenerateImageRequest {
     pub api_key: Option<String>,
@@ -13,9 +13,25 @@ pub struct GenerateImageRequest {
     pub quality: Option<String>,
     pub style: Option<String>,
     pub user: Option<String>,
+    pub seed: Option<u32>,
+    pub naming: Option<OutputNamingOptions>,
+    pub post_process: Option<PostProcessOptions>,
+    #[serde(default)]
+    pub parent_ids: Vec<String>,
+    #[serde(default)]
+    pub template: Option<PromptTemplateRef>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputNamingOptions {
+    pub file_name_template: Option<String>,
+    pub subfolder_template: Option<String>,
+    pub project: Option<String>,
+    pub template_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceImagePayload {
     pub mime_type: Option<String>,
@@ -47,15 +63,190 @@ pub struct UploadImagePayload {
     pub file_name: String,
     pub mime_type: Option<String>,
     pub data_base64: String,
+    pub output_format: Option<String>,
+    pub max_dimension: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SavePromptsPayload {
     pub id: Option<String>,
     pub name: String,
     pub system_prompt: String,
     pub user_prompt: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    /// Input or output images (`input/<id>`, `output/<id>`) showing what the
+    /// template is for; carried along in exported bundles.
+    #[serde(default)]
+    pub sample_images: Vec<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    /// Organisational fields are left unchanged when omitted.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub favorite: Option<bool>,
+    /// Recorded with the version this save creates.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptBundleFormat {
+    #[default]
+    Json,
+    Zip,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPromptTemplatesPayload {
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub format: PromptBundleFormat,
+    pub destination_dir: String,
+    pub file_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPromptTemplatesResult {
+    pub path: String,
+    pub templates: usize,
+    pub assets: usize,
+}
+
+/// What to do when an imported template's id or name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflictStrategy {
+    #[default]
+    Skip,
+    Rename,
+    Overwrite,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPromptTemplatesPayload {
+    pub path: String,
+    #[serde(default)]
+    pub conflict: ImportConflictStrategy,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateImportAction {
+    Created,
+    Renamed,
+    Overwritten,
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateImportChange {
+    pub id: String,
+    pub name: String,
+    pub bundle_id: String,
+    pub bundle_name: String,
+    pub action: TemplateImportAction,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPromptTemplatesReport {
+    pub changes: Vec<TemplateImportChange>,
+    pub assets: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptSearchQuery {
+    pub text: Option<String>,
+    /// Templates must carry every listed tag.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Matches the folder and its subfolders.
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub favorites_only: bool,
+}
+
+/// Identifies the template (and version) a generation was rendered from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplateRef {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+}
+
+/// A value a prompt template expects, referenced as `{{ name }}` in its
+/// system and user prompts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: TemplateVariableKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TemplateVariableKind {
+    Text,
+    #[serde(rename_all = "camelCase")]
+    Enum {
+        options: Vec<String>,
+        /// Accept a list of options, for use with `{% for %}` loops.
+        #[serde(default)]
+        multiple: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Number {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f64>,
+    },
+    /// A reference slot such as `img_1`; renders as its `{img_1}` placeholder.
+    Image,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedPrompt {
+    pub system_prompt: String,
+    pub user_prompt: String,
+}
+
+/// The prompts a new generation starts from. The system prompt also fills in
+/// for requests that leave theirs empty.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultPrompts {
+    pub system_prompt: String,
+    pub user_prompt: String,
+}
+
+/// `None` keeps the current default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDefaultPromptsPayload {
+    pub system_prompt: Option<String>,
+    pub user_prompt: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -64,3 +255,412 @@ pub struct GeneratedImageResponsePayload {
     pub image: StoredImage,
     pub revised_prompt: Option<String>,
 }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportImageMetadataPayload {
+    pub file_name: Option<String>,
+    pub data_base64: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedGeneration {
+    pub source: String,
+    pub request: GenerateImageRequest,
+    pub template: SavePromptsPayload,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    Fit,
+    Fill,
+    Exact,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResizeOptions {
+    pub mode: ResizeMode,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfileMode {
+    #[default]
+    Keep,
+    Strip,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportImagesPayload {
+    pub ids: Vec<String>,
+    pub format: String,
+    pub quality: Option<u8>,
+    #[serde(default)]
+    pub lossless: bool,
+    pub resize: Option<ResizeOptions>,
+    #[serde(default)]
+    pub color_profile: ColorProfileMode,
+    #[serde(default = "default_keep_metadata")]
+    pub keep_metadata: bool,
+    pub destination_dir: String,
+    pub archive_name: Option<String>,
+}
+
+fn default_keep_metadata() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedImage {
+    pub id: String,
+    pub file_name: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportImagesResult {
+    pub files: Vec<ExportedImage>,
+    pub archive_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AspectMode {
+    #[default]
+    Crop,
+    Pad,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FocalPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostProcessOptions {
+    pub aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub aspect_mode: AspectMode,
+    pub focal_point: Option<FocalPoint>,
+    pub pad_color: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditImageRequest {
+    pub api_key: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub base_image_id: String,
+    pub base_library: Option<String>,
+    pub mask_base64: Option<String>,
+    #[serde(default)]
+    pub mask_mode: MaskMode,
+    pub instruction: String,
+    pub size: Option<String>,
+    pub naming: Option<OutputNamingOptions>,
+}
+
+/// How `maskBase64` marks the area to edit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskMode {
+    /// Strokes painted over the image: opaque strokes on a transparent canvas,
+    /// or light strokes on a dark, fully opaque one.
+    #[default]
+    Painted,
+    /// The OpenAI convention: transparent pixels are edited.
+    Alpha,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancePromptRequest {
+    pub api_key: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub prompt: String,
+    /// Replaces the built-in rewrite instruction.
+    pub instruction: Option<String>,
+    #[serde(default)]
+    pub reference_images: Vec<ReferenceImagePayload>,
+    /// How many options to ask for; defaults to 3.
+    pub count: Option<u32>,
+}
+
+/// One rewrite of the prompt. `title` is a short name to save it under as a
+/// template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancedPrompt {
+    pub title: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub explanation: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhancePromptResponse {
+    pub options: Vec<EnhancedPrompt>,
+    pub model: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionImagesRequest {
+    pub api_key: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Input library ids.
+    pub ids: Vec<String>,
+    /// Caption again even when the image already has an up-to-date caption.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Captions for an input image, kept next to it as `<file>.caption.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageCaption {
+    pub short: String,
+    pub detailed: String,
+    pub model: String,
+    /// Hash of the image the caption describes, to spot replaced files.
+    pub content_hash: String,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionedImage {
+    pub id: String,
+    pub caption: ImageCaption,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionImagesResponse {
+    pub captions: Vec<CaptionedImage>,
+    pub failures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreflightSeverity {
+    /// The request would be rejected or fail.
+    Error,
+    /// The request would run, but probably not as intended.
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightCheck {
+    MissingApiKey,
+    EmptyPrompt,
+    EmptySystemPrompt,
+    InvalidReference,
+    DuplicateReference,
+    DuplicateSlot,
+    TooManyReferences,
+    UnresolvedPlaceholder,
+    UnrenderedTemplate,
+    InlineSizeLimit,
+    InvalidPostProcess,
+    InvalidOutputName,
+    UnsupportedModel,
+    UnsupportedParameter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightIssue {
+    pub check: PreflightCheck,
+    pub severity: PreflightSeverity,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreflightReport {
+    pub model: String,
+    pub estimated_tokens: u64,
+    pub text_tokens: u64,
+    pub image_tokens: u64,
+    /// Base64 size of the references sent inline.
+    pub inline_image_bytes: u64,
+    pub inline_limit_bytes: u64,
+    /// References large enough to go through the Files API instead.
+    pub uploaded_image_bytes: u64,
+    pub issues: Vec<PreflightIssue>,
+    /// No issue is an error.
+    pub ready: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartEditSessionRequest {
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub title: Option<String>,
+    pub system_prompt: Option<String>,
+    pub instruction: String,
+    #[serde(default)]
+    pub reference_images: Vec<ReferenceImagePayload>,
+    pub base_image_id: Option<String>,
+    pub naming: Option<OutputNamingOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContinueEditSessionRequest {
+    pub session_id: String,
+    pub api_key: Option<String>,
+    pub instruction: String,
+    #[serde(default)]
+    pub reference_images: Vec<ReferenceImagePayload>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateVariationsRequest {
+    pub api_key: Option<String>,
+    pub output_id: String,
+    pub count: u32,
+    pub strength: Option<f32>,
+    pub model: Option<String>,
+    pub naming: Option<OutputNamingOptions>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateVariationsResponse {
+    pub images: Vec<GeneratedImageResponsePayload>,
+    pub failures: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpscaleImageRequest {
+    pub id: String,
+    pub scale: Option<f32>,
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub filter: ResampleFilter,
+    pub sharpen_sigma: Option<f32>,
+    pub sharpen_threshold: Option<i32>,
+    pub model_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveBackgroundRequest {
+    pub id: String,
+    pub model_path: Option<String>,
+    pub model_input_size: Option<u32>,
+    pub key_color: Option<String>,
+    pub tolerance: Option<u8>,
+    pub feather: Option<f32>,
+    #[serde(default)]
+    pub save_mask: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveBackgroundResponse {
+    pub image: StoredImage,
+    pub mask: Option<StoredImage>,
+    /// Set when the segmentation model could not run and colour keying was
+    /// used instead.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PixelPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Overlay drawn into a prepared reference. Coordinates are pixels in the
+/// cropped and rotated image, before any `max_dimension` downscale.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Annotation {
+    #[serde(rename_all = "camelCase")]
+    Box {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        color: Option<String>,
+        thickness: Option<u32>,
+        label: Option<u32>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Arrow {
+        from: PixelPoint,
+        to: PixelPoint,
+        color: Option<String>,
+        thickness: Option<u32>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Label {
+        x: i32,
+        y: i32,
+        number: u32,
+        color: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrepareReferenceRequest {
+    pub id: String,
+    pub source_library: Option<String>,
+    pub crop: Option<CropRect>,
+    #[serde(default)]
+    pub rotate: i32,
+    #[serde(default)]
+    pub flip_horizontal: bool,
+    #[serde(default)]
+    pub flip_vertical: bool,
+    pub max_dimension: Option<u32>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    pub file_name: Option<String>,
+}
@@ -0,0 +1,291 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+
+use crate::fs_utils::sanitize_file_name;
+use crate::models::OutputNamingOptions;
+
+pub const DEFAULT_FILE_NAME_TEMPLATE: &str = "image_{timestamp}";
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const MAX_SLUG_LENGTH: usize = 48;
+
+/// Values available to `{token}` placeholders in output naming templates.
+pub struct NamingContext<'a> {
+    pub project: Option<&'a str>,
+    pub template_name: Option<&'a str>,
+    pub prompt: &'a str,
+    pub model: &'a str,
+    pub seed: Option<u32>,
+    pub index: usize,
+    pub created_at: DateTime<Local>,
+}
+
+/// A rendered output name: a relative subfolder (possibly empty) and a file
+/// stem that only lacks its extension.
+#[derive(Debug, Clone)]
+pub struct OutputName {
+    pub subfolder: Vec<String>,
+    pub stem: String,
+}
+
+impl OutputName {
+    pub fn file_name(&self, extension: &str) -> String {
+        if extension.is_empty() {
+            self.stem.clone()
+        } else {
+            format!("{}.{}", self.stem, extension)
+        }
+    }
+}
+
+/// Resolves the naming options into a subfolder and file stem. Callers render
+/// the name before any provider call, so a bad template fails the request
+/// before it is paid for.
+pub fn build_output_name(
+    options: Option<&OutputNamingOptions>,
+    context: &NamingContext,
+) -> Result<OutputName, String> {
+    let file_template = options
+        .and_then(|options| options.file_name_template.as_deref())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or(DEFAULT_FILE_NAME_TEMPLATE);
+
+    let rendered = render_template(file_template, context)?;
+    let stem = sanitize_segment(&rendered).ok_or_else(|| {
+        format!(
+            "File name template '{}' produced an invalid name: '{}'.",
+            file_template, rendered
+        )
+    })?;
+
+    let subfolder = match options
+        .and_then(|options| options.subfolder_template.as_deref())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(template) => render_template(template, context)?
+            .split(['/', '\\'])
+            .filter_map(sanitize_segment)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(OutputName { subfolder, stem })
+}
+
+/// Expands `{project}`, `{template_name}`, `{prompt_slug}`, `{model}`,
+/// `{seed}`, `{timestamp}`, `{date}` / `{date:<strftime>}` and `{index}` /
+/// `{index:<width>}`.
+pub fn render_template(template: &str, context: &NamingContext) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unclosed '{{' in naming template '{}'.", template))?;
+
+        let token = &after[..end];
+        let (name, argument) = match token.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument)),
+            None => (token.trim(), None),
+        };
+        output.push_str(&render_token(name, argument, context)?);
+
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn render_token(
+    name: &str,
+    argument: Option<&str>,
+    context: &NamingContext,
+) -> Result<String, String> {
+    let value = match name {
+        "project" => context.project.unwrap_or_default().to_string(),
+        "template_name" => context.template_name.unwrap_or_default().to_string(),
+        "prompt_slug" => slugify(context.prompt),
+        "model" => context.model.to_string(),
+        "seed" => context
+            .seed
+            .map(|seed| seed.to_string())
+            .unwrap_or_default(),
+        "timestamp" => context.created_at.timestamp_millis().to_string(),
+        "date" => {
+            let format = argument.unwrap_or(DEFAULT_DATE_FORMAT);
+            let items: Vec<Item> = StrftimeItems::new(format).collect();
+            if items.iter().any(|item| matches!(item, Item::Error)) {
+                return Err(format!("Invalid date format '{}'.", format));
+            }
+            context
+                .created_at
+                .format_with_items(items.into_iter())
+                .to_string()
+        }
+        "index" => match argument {
+            Some(width) => {
+                let width: usize = width
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid index width '{}'.", width))?;
+                format!("{:0width$}", context.index)
+            }
+            None => context.index.to_string(),
+        },
+        other => return Err(format!("Unknown naming token '{{{}}}'.", other)),
+    };
+
+    Ok(value)
+}
+
+/// Lowercase words joined by `-`, at most `MAX_SLUG_LENGTH` characters; safe
+/// as a path segment.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if slug.is_empty() && word.len() > MAX_SLUG_LENGTH {
+            slug.extend(word.to_lowercase().chars().take(MAX_SLUG_LENGTH));
+            break;
+        }
+        if slug.len() + word.len() + 1 > MAX_SLUG_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_lowercase());
+    }
+    slug
+}
+
+/// Replaces characters that are unsafe on common file systems and trims
+/// separators left dangling by empty tokens.
+fn sanitize_segment(segment: &str) -> Option<String> {
+    let cleaned: String = segment
+        .chars()
+        .map(|ch| match ch {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect();
+
+    let trimmed =
+        cleaned.trim_matches(|ch: char| ch == '-' || ch == '_' || ch == '.' || ch.is_whitespace());
+    sanitize_file_name(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context(project: Option<&str>) -> NamingContext<'_> {
+        NamingContext {
+            project,
+            template_name: Some("Portrait"),
+            prompt: "A red fox, sitting in the snow!",
+            model: "gemini-2.5-flash-image",
+            seed: None,
+            index: 7,
+            created_at: Local.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).unwrap(),
+        }
+    }
+
+    fn naming(file: &str, subfolder: Option<&str>) -> OutputNamingOptions {
+        OutputNamingOptions {
+            file_name_template: Some(file.to_string()),
+            subfolder_template: subfolder.map(str::to_string),
+            ..OutputNamingOptions::default()
+        }
+    }
+
+    #[test]
+    fn tokens_are_expanded() {
+        let rendered = render_template(
+            "{template_name}_{prompt_slug}_{date}_{index:3}_{model}",
+            &context(None),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "Portrait_a-red-fox-sitting-in-the-snow_2024-03-05_007_gemini-2.5-flash-image"
+        );
+    }
+
+    #[test]
+    fn date_takes_a_strftime_format() {
+        let rendered = render_template("{date:%Y%m%d-%H%M}", &context(None)).unwrap();
+        assert_eq!(rendered, "20240305-1407");
+    }
+
+    #[test]
+    fn malformed_templates_are_errors() {
+        for template in ["image_{index", "{nope}", "{index:wide}", "{date:%Q}"] {
+            assert!(
+                render_template(template, &context(None)).is_err(),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_rendered_name_is_an_error() {
+        let options = naming("{seed}", None);
+        assert!(build_output_name(Some(&options), &context(None)).is_err());
+    }
+
+    #[test]
+    fn file_name_cannot_contain_path_separators() {
+        let options = naming("{project}_{index}", None);
+        assert!(build_output_name(Some(&options), &context(Some("a/b"))).is_err());
+    }
+
+    #[test]
+    fn subfolder_separators_become_folders_and_unsafe_segments_are_dropped() {
+        let options = naming("img:{index}", Some("{project}/../<drafts>"));
+        let name = build_output_name(Some(&options), &context(Some("clients\\acme"))).unwrap();
+
+        assert_eq!(name.subfolder, vec!["clients", "acme", "drafts"]);
+        assert_eq!(name.stem, "img_7");
+        assert_eq!(name.file_name("png"), "img_7.png");
+    }
+
+    #[test]
+    fn default_template_uses_the_timestamp() {
+        let name = build_output_name(None, &context(None)).unwrap();
+        assert_eq!(
+            name.stem,
+            format!("image_{}", context(None).created_at.timestamp_millis())
+        );
+        assert!(name.subfolder.is_empty());
+    }
+
+    #[test]
+    fn slugs_stop_at_a_word_boundary() {
+        let slug = slugify(&"watercolour landscape ".repeat(10));
+        assert!(slug.len() <= MAX_SLUG_LENGTH);
+        assert!(slug.ends_with("landscape") || slug.ends_with("watercolour"));
+        assert!(!slug.ends_with('-'));
+    }
+
+    #[test]
+    fn long_first_word_is_truncated() {
+        let slug = slugify(&"A".repeat(60));
+        assert_eq!(slug, "a".repeat(MAX_SLUG_LENGTH));
+    }
+
+    #[test]
+    fn slug_of_punctuation_is_empty() {
+        assert_eq!(slugify("?!  --"), "");
+    }
+}
@@ -0,0 +1,172 @@
+//! CPU inference for user-supplied ONNX image models. Only available when the
+//! crate is built with the `onnx` feature; otherwise every entry point reports
+//! that support is missing so callers can fall back to the pure-Rust paths.
+
+use std::path::Path;
+
+use image::{DynamicImage, GrayImage};
+
+#[cfg(feature = "onnx")]
+use image::imageops::FilterType;
+#[cfg(feature = "onnx")]
+use image::{Luma, Rgb, RgbImage, RgbaImage};
+#[cfg(feature = "onnx")]
+use tract_onnx::prelude::*;
+
+/// Runs a super-resolution model taking and returning NCHW RGB `f32` tensors
+/// in the 0-1 range. The output scale is whatever the model produces; any
+/// alpha channel is resampled separately and re-attached.
+#[cfg(feature = "onnx")]
+pub fn run_super_resolution(
+    model_path: &Path,
+    image: &DynamicImage,
+) -> Result<DynamicImage, String> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let model = load_model(model_path, [1, 3, height, width])?;
+
+    let rgb = image.to_rgb8();
+    let input: Tensor =
+        tract_ndarray::Array4::from_shape_fn((1, 3, height, width), |(_, channel, y, x)| {
+            f32::from(rgb.get_pixel(x as u32, y as u32)[channel]) / 255.0
+        })
+        .into();
+
+    let outputs = model
+        .run(tvec!(input.into()))
+        .map_err(|err| format!("ONNX inference failed: {}", err))?;
+    let output = outputs[0]
+        .to_array_view::<f32>()
+        .map_err(|err| format!("Unexpected ONNX output type: {}", err))?;
+
+    let shape = output.shape();
+    if shape.len() != 4 || shape[1] != 3 {
+        return Err(format!(
+            "Expected a [1, 3, H, W] model output, got {:?}.",
+            shape
+        ));
+    }
+    let (out_height, out_width) = (shape[2] as u32, shape[3] as u32);
+
+    let upscaled = RgbImage::from_fn(out_width, out_height, |x, y| {
+        let channel = |index: usize| {
+            (output[[0, index, y as usize, x as usize]].clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Rgb([channel(0), channel(1), channel(2)])
+    });
+
+    if !image.color().has_alpha() {
+        return Ok(DynamicImage::ImageRgb8(upscaled));
+    }
+
+    let alpha = image.to_rgba8();
+    let alpha = image::imageops::resize(&alpha, out_width, out_height, FilterType::Lanczos3);
+    let combined = RgbaImage::from_fn(out_width, out_height, |x, y| {
+        let Rgb([red, green, blue]) = *upscaled.get_pixel(x, y);
+        image::Rgba([red, green, blue, alpha.get_pixel(x, y)[3]])
+    });
+    Ok(DynamicImage::ImageRgba8(combined))
+}
+
+#[cfg(not(feature = "onnx"))]
+pub fn run_super_resolution(
+    _model_path: &Path,
+    _image: &DynamicImage,
+) -> Result<DynamicImage, String> {
+    Err(missing_support())
+}
+
+/// Runs a salient-object segmentation model (U²-Net / IS-Net style) on a
+/// square `input_size` RGB tensor with ImageNet normalisation. The first
+/// output channel is min-max normalised and resized back to the image, giving
+/// a foreground mask where 255 means keep.
+#[cfg(feature = "onnx")]
+pub fn run_segmentation(
+    model_path: &Path,
+    image: &DynamicImage,
+    input_size: u32,
+) -> Result<GrayImage, String> {
+    const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+    const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+    let side = input_size as usize;
+    let model = load_model(model_path, [1, 3, side, side])?;
+
+    let rgb = image::imageops::resize(
+        &image.to_rgb8(),
+        input_size,
+        input_size,
+        FilterType::Triangle,
+    );
+    let input: Tensor = tract_ndarray::Array4::from_shape_fn((1, 3, side, side), |(_, c, y, x)| {
+        let value = f32::from(rgb.get_pixel(x as u32, y as u32)[c]) / 255.0;
+        (value - MEAN[c]) / STD[c]
+    })
+    .into();
+
+    let outputs = model
+        .run(tvec!(input.into()))
+        .map_err(|err| format!("ONNX inference failed: {}", err))?;
+    let output = outputs[0]
+        .to_array_view::<f32>()
+        .map_err(|err| format!("Unexpected ONNX output type: {}", err))?;
+
+    let shape = output.shape();
+    if shape.len() != 4 || shape[1] < 1 {
+        return Err(format!(
+            "Expected a [1, 1, H, W] model output, got {:?}.",
+            shape
+        ));
+    }
+    let (out_height, out_width) = (shape[2], shape[3]);
+
+    let (min, max) = output
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+    let range = (max - min).max(f32::EPSILON);
+
+    let mask = GrayImage::from_fn(out_width as u32, out_height as u32, |x, y| {
+        let value = (output[[0, 0, y as usize, x as usize]] - min) / range;
+        Luma([(value * 255.0).round() as u8])
+    });
+    Ok(image::imageops::resize(
+        &mask,
+        image.width(),
+        image.height(),
+        FilterType::Triangle,
+    ))
+}
+
+#[cfg(not(feature = "onnx"))]
+pub fn run_segmentation(
+    _model_path: &Path,
+    _image: &DynamicImage,
+    _input_size: u32,
+) -> Result<GrayImage, String> {
+    Err(missing_support())
+}
+
+#[cfg(feature = "onnx")]
+fn load_model(
+    model_path: &Path,
+    input_shape: [usize; 4],
+) -> Result<TypedRunnableModel<TypedModel>, String> {
+    tract_onnx::onnx()
+        .model_for_path(model_path)
+        .and_then(|model| model.with_input_fact(0, f32::fact(input_shape).into()))
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(|err| {
+            format!(
+                "Unable to load ONNX model '{}': {}",
+                model_path.display(),
+                err
+            )
+        })
+}
+
+#[cfg(not(feature = "onnx"))]
+fn missing_support() -> String {
+    "ONNX models are not supported by this build; rebuild with the `onnx` feature.".to_string()
+}
@@ -0,0 +1,331 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::constants::MAX_OUTPUT_DIMENSION;
+use crate::models::{AspectMode, FocalPoint, PostProcessOptions, ResampleFilter};
+
+const DEFAULT_PAD_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const CENTER: FocalPoint = FocalPoint { x: 0.5, y: 0.5 };
+
+/// Checks the options without an image, so a generation request can be
+/// rejected before the provider is paid for an output it would discard.
+pub fn validate_post_process(options: &PostProcessOptions) -> Result<(), String> {
+    if let Some(value) = options
+        .aspect_ratio
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        parse_aspect_ratio(value)?;
+    }
+    if let Some(value) = options.pad_color.as_deref() {
+        parse_color(value)?;
+    }
+    for size in [options.width, options.height].into_iter().flatten() {
+        check_output_dimension(size)?;
+    }
+    Ok(())
+}
+
+/// Applies the per-request post-processing steps in order: match the aspect
+/// ratio (crop around the focal point or pad), then resize to the requested
+/// pixel size. When only `width` and `height` are given their ratio is used
+/// as the target aspect; alongside an explicit aspect ratio they are a box
+/// the result is fitted into. Either way the final resize never distorts the
+/// image.
+pub fn apply_post_process(
+    image: DynamicImage,
+    options: &PostProcessOptions,
+) -> Result<DynamicImage, String> {
+    validate_post_process(options)?;
+    let explicit_aspect = options
+        .aspect_ratio
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let aspect = match explicit_aspect {
+        Some(value) => Some(parse_aspect_ratio(value)?),
+        None => match (options.width, options.height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                Some(width as f64 / height as f64)
+            }
+            _ => None,
+        },
+    };
+
+    let mut image = image;
+
+    if let Some(aspect) = aspect {
+        image = match options.aspect_mode {
+            AspectMode::Crop => {
+                crop_to_aspect(&image, aspect, options.focal_point.unwrap_or(CENTER))
+            }
+            AspectMode::Pad => {
+                let color = match options.pad_color.as_deref() {
+                    Some(value) => parse_color(value)?,
+                    None => DEFAULT_PAD_COLOR,
+                };
+                pad_to_aspect(&image, aspect, color)?
+            }
+        };
+    }
+
+    let filter = filter_type(options.filter);
+    image = match (options.width, options.height) {
+        (Some(width), Some(height)) if explicit_aspect.is_some() => {
+            image.resize(width, height, filter)
+        }
+        (Some(width), Some(height)) => image.resize_exact(width, height, filter),
+        (Some(width), None) => {
+            let height = scaled_dimension(image.height(), width, image.width());
+            check_output_dimension(height)?;
+            image.resize_exact(width, height, filter)
+        }
+        (None, Some(height)) => {
+            let width = scaled_dimension(image.width(), height, image.height());
+            check_output_dimension(width)?;
+            image.resize_exact(width, height, filter)
+        }
+        (None, None) => image,
+    };
+
+    Ok(image)
+}
+
+/// Accepts `16:9`, `16/9`, `2048x1200` or a plain decimal ratio.
+pub fn parse_aspect_ratio(value: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid aspect ratio '{}'.", value);
+
+    let ratio = match value.trim().split_once([':', '/', 'x', 'X']) {
+        Some((width, height)) => {
+            let width: f64 = width.trim().parse().map_err(|_| invalid())?;
+            let height: f64 = height.trim().parse().map_err(|_| invalid())?;
+            width / height
+        }
+        None => value.trim().parse().map_err(|_| invalid())?,
+    };
+
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(ratio)
+}
+
+/// Accepts `#rgb`, `#rrggbb`, `#rrggbbaa` or `transparent`.
+pub fn parse_color(value: &str) -> Result<Rgba<u8>, String> {
+    let trimmed = value.trim();
+    if trimmed.eq_ignore_ascii_case("transparent") {
+        return Ok(Rgba([0, 0, 0, 0]));
+    }
+
+    let invalid = || format!("Invalid colour '{}'.", value);
+    let hex = trimmed.strip_prefix('#').unwrap_or(trimmed);
+    if !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let channel =
+        |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).map_err(|_| invalid());
+
+    match hex.len() {
+        3 => {
+            let mut rgba = [255u8; 4];
+            for (index, slot) in rgba.iter_mut().take(3).enumerate() {
+                *slot = channel(index..index + 1)? * 17;
+            }
+            Ok(Rgba(rgba))
+        }
+        6 | 8 => {
+            let mut rgba = [255u8; 4];
+            for (index, slot) in rgba.iter_mut().take(hex.len() / 2).enumerate() {
+                *slot = channel(index * 2..index * 2 + 2)?;
+            }
+            Ok(Rgba(rgba))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn crop_to_aspect(image: &DynamicImage, aspect: f64, focal: FocalPoint) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let current = width as f64 / height as f64;
+
+    let (crop_width, crop_height) = if current > aspect {
+        (
+            ((height as f64 * aspect).round() as u32).clamp(1, width),
+            height,
+        )
+    } else {
+        (
+            width,
+            ((width as f64 / aspect).round() as u32).clamp(1, height),
+        )
+    };
+
+    let x = window_start(width, crop_width, focal.x);
+    let y = window_start(height, crop_height, focal.y);
+    image.crop_imm(x, y, crop_width, crop_height)
+}
+
+/// Positions a window of `window` pixels so it is centred on `focal` (0-1)
+/// without leaving the `total` range.
+fn window_start(total: u32, window: u32, focal: f32) -> u32 {
+    let centre = f64::from(focal.clamp(0.0, 1.0)) * total as f64;
+    let start = (centre - window as f64 / 2.0).round().max(0.0) as u32;
+    start.min(total - window)
+}
+
+fn pad_to_aspect(
+    image: &DynamicImage,
+    aspect: f64,
+    color: Rgba<u8>,
+) -> Result<DynamicImage, String> {
+    let (width, height) = (image.width(), image.height());
+    let current = width as f64 / height as f64;
+
+    let (canvas_width, canvas_height) = if current > aspect {
+        (
+            width as f64,
+            (width as f64 / aspect).round().max(height as f64),
+        )
+    } else {
+        (
+            (height as f64 * aspect).round().max(width as f64),
+            height as f64,
+        )
+    };
+    if canvas_width.max(canvas_height) > MAX_OUTPUT_DIMENSION as f64 {
+        return Err(format!(
+            "Padding to this aspect ratio needs a {}x{} canvas, over the {} px limit.",
+            canvas_width, canvas_height, MAX_OUTPUT_DIMENSION
+        ));
+    }
+    let (canvas_width, canvas_height) = (canvas_width as u32, canvas_height as u32);
+
+    let mut canvas = RgbaImage::from_pixel(canvas_width, canvas_height, color);
+    imageops::overlay(
+        &mut canvas,
+        &image.to_rgba8(),
+        i64::from((canvas_width - width) / 2),
+        i64::from((canvas_height - height) / 2),
+    );
+    Ok(DynamicImage::ImageRgba8(canvas))
+}
+
+fn check_output_dimension(size: u32) -> Result<(), String> {
+    if size == 0 {
+        return Err("Post-process width and height must be greater than zero.".into());
+    }
+    if size > MAX_OUTPUT_DIMENSION {
+        return Err(format!(
+            "Post-process width and height must be at most {} px, got {}.",
+            MAX_OUTPUT_DIMENSION, size
+        ));
+    }
+    Ok(())
+}
+
+fn scaled_dimension(other: u32, target: u32, reference: u32) -> u32 {
+    ((other as f64 * target as f64 / reference as f64).round() as u32).max(1)
+}
+
+pub fn filter_type(filter: ResampleFilter) -> FilterType {
+    match filter {
+        ResampleFilter::Nearest => FilterType::Nearest,
+        ResampleFilter::Triangle => FilterType::Triangle,
+        ResampleFilter::CatmullRom => FilterType::CatmullRom,
+        ResampleFilter::Gaussian => FilterType::Gaussian,
+        ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(
+        aspect_ratio: Option<&str>,
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> PostProcessOptions {
+        PostProcessOptions {
+            aspect_ratio: aspect_ratio.map(str::to_string),
+            aspect_mode: AspectMode::Crop,
+            focal_point: None,
+            pad_color: None,
+            width,
+            height,
+            filter: ResampleFilter::Nearest,
+        }
+    }
+
+    #[test]
+    fn aspect_ratios_parse_in_every_notation() {
+        assert_eq!(parse_aspect_ratio("16:9").unwrap(), 16.0 / 9.0);
+        assert_eq!(parse_aspect_ratio(" 4/3 ").unwrap(), 4.0 / 3.0);
+        assert_eq!(parse_aspect_ratio("2048x1024").unwrap(), 2.0);
+        assert_eq!(parse_aspect_ratio("1.5").unwrap(), 1.5);
+    }
+
+    #[test]
+    fn degenerate_aspect_ratios_are_rejected() {
+        for value in ["", "wide", "16:", "0:1", "1:0", "-4:3", "NaN"] {
+            assert!(parse_aspect_ratio(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn colours_parse_in_every_notation() {
+        assert_eq!(parse_color("#fff").unwrap(), Rgba([255, 255, 255, 255]));
+        assert_eq!(
+            parse_color("#1a2b3c").unwrap(),
+            Rgba([0x1a, 0x2b, 0x3c, 255])
+        );
+        assert_eq!(parse_color("ff000080").unwrap(), Rgba([255, 0, 0, 0x80]));
+        assert_eq!(parse_color("Transparent").unwrap(), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn malformed_colours_are_rejected() {
+        for value in ["", "#ff", "#12345", "#gggggg", "#ééé"] {
+            assert!(parse_color(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn window_is_centred_on_the_focal_point_and_kept_in_range() {
+        assert_eq!(window_start(100, 40, 0.5), 30);
+        assert_eq!(window_start(100, 40, 0.0), 0);
+        assert_eq!(window_start(100, 40, 1.0), 60);
+        assert_eq!(window_start(100, 40, 2.0), 60);
+        assert_eq!(window_start(100, 100, 0.3), 0);
+    }
+
+    #[test]
+    fn width_and_height_alone_crop_to_their_ratio() {
+        let image = DynamicImage::new_rgba8(200, 100);
+        let result = apply_post_process(image, &options(None, Some(50), Some(50))).unwrap();
+        assert_eq!((result.width(), result.height()), (50, 50));
+    }
+
+    #[test]
+    fn aspect_ratio_with_a_mismatched_box_fits_inside_it() {
+        let image = DynamicImage::new_rgba8(200, 100);
+        let result = apply_post_process(image, &options(Some("1:1"), Some(50), Some(100))).unwrap();
+        assert_eq!((result.width(), result.height()), (50, 50));
+    }
+
+    #[test]
+    fn single_dimension_keeps_the_aspect_ratio() {
+        let image = DynamicImage::new_rgba8(200, 100);
+        let result = apply_post_process(image, &options(Some("16:9"), Some(160), None)).unwrap();
+        assert_eq!((result.width(), result.height()), (160, 90));
+    }
+
+    #[test]
+    fn oversized_output_is_rejected() {
+        let options = options(None, Some(MAX_OUTPUT_DIMENSION + 1), None);
+        assert!(validate_post_process(&options).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! JSON documents on disk shared by concurrent commands. Each file has its
+//! own async lock; saves go through a temp file and a rename so a crash never
+//! leaves a half-written document, and the previous version is kept as
+//! `<name>.bak`. A document that no longer parses is reported by read-only
+//! loads and left untouched. `open` moves it aside and restores the backup;
+//! when there is no usable backup it fails once, naming the moved file, and
+//! the next call starts afresh.
+//!
+//! Documents are stored as `{"schemaVersion": n, "data": ...}`. Older files
+//! are upgraded through `migrations` when opened for writing, after copying
+//! the original to `<name>.v<n>.bak`; read-only loads upgrade in memory only.
+//! Files from a newer build are refused rather than rewritten.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+use crate::migrations::{migrate, Versioned};
+
+static LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// A loaded document that holds its file's lock until dropped, so nothing
+/// else can read or write it between loading and `save`.
+pub struct Document<T> {
+    path: PathBuf,
+    value: T,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl<T> Deref for Document<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Document<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Versioned> Document<T> {
+    pub async fn save(&self) -> Result<(), String> {
+        write_document(&self.path, &self.value, true).await
+    }
+}
+
+/// Locks and loads `path` for a read-modify-write; a missing file yields the
+/// default value.
+pub async fn open<T: Default + Versioned>(path: &Path) -> Result<Document<T>, String> {
+    let guard = lock(path).await;
+    let value = read_document(path, true).await?.unwrap_or_default();
+    Ok(Document {
+        path: path.to_path_buf(),
+        value,
+        _guard: guard,
+    })
+}
+
+/// Like `open`, but distinguishes a missing file.
+pub async fn open_optional<T: Versioned>(path: &Path) -> Result<Option<Document<T>>, String> {
+    let guard = lock(path).await;
+    Ok(read_document(path, true).await?.map(|value| Document {
+        path: path.to_path_buf(),
+        value,
+        _guard: guard,
+    }))
+}
+
+pub async fn load<T: Default + Versioned>(path: &Path) -> Result<T, String> {
+    Ok(load_optional(path).await?.unwrap_or_default())
+}
+
+/// Like `load`, but distinguishes a missing file.
+pub async fn load_optional<T: Versioned>(path: &Path) -> Result<Option<T>, String> {
+    let _guard = lock(path).await;
+    read_document(path, false).await
+}
+
+/// Replaces the whole document at `path`.
+pub async fn save<T: Versioned>(path: &Path, value: &T) -> Result<(), String> {
+    let _guard = lock(path).await;
+    write_document(path, value, true).await
+}
+
+/// Deletes the document at `path` and its backup; missing files are fine.
+pub async fn remove(path: &Path) -> Result<(), String> {
+    let _guard = lock(path).await;
+    for target in [path.to_path_buf(), sibling(path, "bak")] {
+        match tokio::fs::remove_file(&target).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("Unable to delete '{}': {}", target.display(), err)),
+        }
+    }
+    Ok(())
+}
+
+async fn lock(path: &Path) -> OwnedMutexGuard<()> {
+    let mutex = LOCKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+    mutex.lock_owned().await
+}
+
+/// The on-disk shape of every document. Files without it predate versioning
+/// and are read as schema version 1.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<'a, T> {
+    schema_version: u32,
+    data: &'a T,
+}
+
+enum DecodeError {
+    /// Not JSON, or data that doesn't match the document type.
+    Unreadable(String),
+    /// Readable, but unsafe to touch: written by a newer build, or an upgrade
+    /// step failed.
+    Unsupported(String),
+}
+
+/// A decoded document, and the schema version it was stored at.
+struct Decoded<T> {
+    value: T,
+    version: u32,
+}
+
+/// Reads and upgrades the document at `path`. The file itself only changes
+/// when `for_write` is set, i.e. the caller is about to save it: an older
+/// version is rewritten after a backup, and an unreadable one moved aside.
+async fn read_document<T: Versioned>(path: &Path, for_write: bool) -> Result<Option<T>, String> {
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Unable to read '{}': {}", path.display(), err)),
+    };
+    if contents.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    let err = match decode::<T>(&contents) {
+        Ok(Decoded { value, version }) if version == T::SCHEMA_VERSION || !for_write => {
+            return Ok(Some(value))
+        }
+        Ok(Decoded { value, version }) => {
+            let backup_path = unused_sibling(path, &format!("v{}.bak", version));
+            tokio::fs::copy(path, &backup_path).await.map_err(|err| {
+                format!(
+                    "Unable to back up '{}' before upgrading it: {}",
+                    path.display(),
+                    err
+                )
+            })?;
+            write_document(path, &value, false).await?;
+            eprintln!(
+                "Upgraded '{}' from schema version {} to {}; the original is kept as '{}'.",
+                path.display(),
+                version,
+                T::SCHEMA_VERSION,
+                backup_path.display()
+            );
+            return Ok(Some(value));
+        }
+        Err(DecodeError::Unsupported(err)) => {
+            return Err(format!("Unable to load '{}': {}", path.display(), err))
+        }
+        Err(DecodeError::Unreadable(err)) if !for_write => {
+            return Err(format!(
+                "Unable to load '{}': {}. The file was left unchanged.",
+                path.display(),
+                err
+            ))
+        }
+        Err(DecodeError::Unreadable(err)) => err,
+    };
+
+    let quarantined = unused_sibling(path, &format!("corrupt-{}", unix_timestamp_millis()));
+    tokio::fs::rename(path, &quarantined)
+        .await
+        .map_err(|rename_err| {
+            format!(
+                "Unable to parse '{}' ({}) or move it aside: {}",
+                path.display(),
+                err,
+                rename_err
+            )
+        })?;
+    eprintln!(
+        "Moved unreadable '{}' to '{}': {}",
+        path.display(),
+        quarantined.display(),
+        err
+    );
+
+    let backup_path = sibling(path, "bak");
+    let restore_failure = match tokio::fs::read(&backup_path).await {
+        Ok(backup) => match decode::<T>(&backup) {
+            Ok(Decoded { value, .. }) => {
+                eprintln!("Restored '{}' from its backup.", path.display());
+                write_document(path, &value, false).await?;
+                return Ok(Some(value));
+            }
+            Err(DecodeError::Unreadable(err) | DecodeError::Unsupported(err)) => {
+                format!("its backup is unusable too ({})", err)
+            }
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => "it has no backup".to_string(),
+        Err(err) => format!("its backup could not be read ({})", err),
+    };
+
+    Err(format!(
+        "'{}' was unreadable ({}) and {}. It was moved to '{}'; try again to start a new one.",
+        path.display(),
+        err,
+        restore_failure,
+        quarantined.display()
+    ))
+}
+
+/// Parses a stored document, running migrations when it is older than the
+/// current schema.
+fn decode<T: Versioned>(contents: &[u8]) -> Result<Decoded<T>, DecodeError> {
+    let parsed: Value =
+        serde_json::from_slice(contents).map_err(|err| DecodeError::Unreadable(err.to_string()))?;
+
+    let (version, data) = match parsed {
+        Value::Object(mut envelope)
+            if envelope.len() == 2
+                && envelope.contains_key("schemaVersion")
+                && envelope.contains_key("data") =>
+        {
+            let version = envelope
+                .get("schemaVersion")
+                .and_then(Value::as_u64)
+                .and_then(|version| u32::try_from(version).ok())
+                .filter(|version| *version > 0)
+                .ok_or_else(|| {
+                    DecodeError::Unreadable("schemaVersion is not a positive integer".to_string())
+                })?;
+            let data = envelope.remove("data").unwrap_or_default();
+            (version, data)
+        }
+        legacy => (1, legacy),
+    };
+
+    if version > T::SCHEMA_VERSION {
+        return Err(DecodeError::Unsupported(format!(
+            "it was saved by a newer version of the app (schema version {}, this build \
+             supports up to {}). Update the app to open it.",
+            version,
+            T::SCHEMA_VERSION
+        )));
+    }
+
+    let data = migrate::<T>(data, version).map_err(DecodeError::Unsupported)?;
+    let value =
+        serde_json::from_value(data).map_err(|err| DecodeError::Unreadable(err.to_string()))?;
+    Ok(Decoded { value, version })
+}
+
+async fn write_document<T: Versioned>(path: &Path, value: &T, backup: bool) -> Result<(), String> {
+    let payload = serde_json::to_vec_pretty(&Envelope {
+        schema_version: T::SCHEMA_VERSION,
+        data: value,
+    })
+    .map_err(|err| format!("Unable to serialise '{}': {}", path.display(), err))?;
+    write_bytes(path, payload, backup).await
+}
+
+/// Writes `<name>.tmp`, flushes it to disk, optionally copies the current file
+/// to `<name>.bak`, then renames the temp file over the original.
+async fn write_bytes(path: &Path, payload: Vec<u8>, backup: bool) -> Result<(), String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let temp_path = sibling(&path, "tmp");
+        let mut file = std::fs::File::create(&temp_path)
+            .and_then(|mut file| file.write_all(&payload).map(|_| file))
+            .map_err(|err| format!("Unable to write '{}': {}", temp_path.display(), err))?;
+        file.flush()
+            .and_then(|_| file.sync_all())
+            .map_err(|err| format!("Unable to flush '{}': {}", temp_path.display(), err))?;
+        drop(file);
+
+        if backup && path.exists() {
+            let backup_path = sibling(&path, "bak");
+            std::fs::copy(&path, &backup_path).map_err(|err| {
+                format!("Unable to back up to '{}': {}", backup_path.display(), err)
+            })?;
+        }
+
+        std::fs::rename(&temp_path, &path)
+            .map_err(|err| format!("Unable to replace '{}': {}", path.display(), err))
+    })
+    .await
+    .map_err(|err| format!("Store write task failed: {}", err))?
+}
+
+/// `<name>.<suffix>` next to `path`, e.g. `log.json.bak`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Like `sibling`, adding a counter when that name is taken so earlier
+/// copies are never overwritten.
+fn unused_sibling(path: &Path, suffix: &str) -> PathBuf {
+    (0..)
+        .map(|attempt| match attempt {
+            0 => sibling(path, suffix),
+            _ => sibling(path, &format!("{}-{}", suffix, attempt)),
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("an unused file name exists")
+}
+
+fn unix_timestamp_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::prompts::PromptTemplates;
+
+    /// A prompts file as written before documents were versioned.
+    const LEGACY_PROMPTS: &str = r#"[{"id":"p1","name":"Portrait","systemPrompt":"","userPrompt":"A portrait of {img_1}","dateCreated":1700000000}]"#;
+
+    fn scratch_file(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("image-gen-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create scratch dir");
+        let path = dir.join("prompts.json");
+        std::fs::write(&path, LEGACY_PROMPTS).expect("write legacy file");
+        path
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime")
+            .block_on(future)
+    }
+
+    #[test]
+    fn open_upgrades_legacy_file_and_keeps_a_backup() {
+        let path = scratch_file("upgrade");
+
+        let templates = block_on(open::<Vec<PromptTemplates>>(&path)).expect("open");
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].id, "p1");
+        assert_eq!(templates[0].user_prompt, "A portrait of {img_1}");
+        drop(templates);
+
+        let backup = std::fs::read_to_string(sibling(&path, "v1.bak")).expect("upgrade backup");
+        assert_eq!(backup, LEGACY_PROMPTS);
+
+        let stored: Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).expect("stored JSON");
+        assert_eq!(stored["schemaVersion"], 2);
+        assert_eq!(stored["data"][0]["id"], "p1");
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn load_leaves_legacy_file_untouched() {
+        let path = scratch_file("read-only");
+
+        let templates = block_on(load::<Vec<PromptTemplates>>(&path)).expect("load");
+        assert_eq!(templates[0].name, "Portrait");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), LEGACY_PROMPTS);
+        assert!(!sibling(&path, "v1.bak").exists());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
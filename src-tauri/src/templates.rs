@@ -0,0 +1,324 @@
+//! Prompt template rendering. Templates use MiniJinja syntax (`{{ name }}`,
+//! `{% if %}`, `{% for %}`); the legacy `{img_N}` placeholders are left alone
+//! because single braces are plain text to the engine. Captions of bound
+//! reference images are exposed as `captions.<slot>`.
+
+use std::collections::HashSet;
+
+use minijinja::Environment;
+use serde_json::{Map, Value};
+
+use crate::models::{TemplateVariable, TemplateVariableKind};
+
+/// Render variable holding the captions of bound reference images, keyed by
+/// slot; templates can't declare a variable with this name.
+pub const CAPTIONS_VARIABLE: &str = "captions";
+
+/// Checks variable declarations and template syntax before a template is saved.
+pub fn validate_template(sources: &[&str], variables: &[TemplateVariable]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for variable in variables {
+        let name = variable.name.as_str();
+        let valid_name = name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && name
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || character == '_');
+        if !valid_name {
+            return Err(format!(
+                "Invalid template variable name '{}'; use letters, digits and underscores.",
+                name
+            ));
+        }
+        if name == CAPTIONS_VARIABLE {
+            return Err(format!(
+                "Template variable name '{}' is reserved for image captions.",
+                name
+            ));
+        }
+        if !names.insert(name) {
+            return Err(format!("Template variable '{}' is declared twice.", name));
+        }
+        if let TemplateVariableKind::Enum { options, .. } = &variable.kind {
+            if options.is_empty() {
+                return Err(format!("Template variable '{}' has no options.", name));
+            }
+        }
+        if let Some(default) = &variable.default {
+            validate_value(variable, default)?;
+        }
+    }
+
+    let environment = environment();
+    for source in sources {
+        environment
+            .template_from_str(source)
+            .map_err(|err| format!("Invalid prompt template: {}", err))?;
+    }
+    Ok(())
+}
+
+/// Builds the render context from user `values`, falling back to defaults.
+/// Optional variables without a value stay undefined so `{% if %}` can test
+/// for them.
+pub fn resolve_variables(
+    variables: &[TemplateVariable],
+    values: &Map<String, Value>,
+) -> Result<Map<String, Value>, String> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|key| !variables.iter().any(|variable| &variable.name == *key))
+    {
+        return Err(format!("Unknown template variable '{}'.", unknown));
+    }
+
+    let mut context = Map::new();
+    for variable in variables {
+        let value = values
+            .get(&variable.name)
+            .filter(|value| !value.is_null())
+            .or(variable.default.as_ref());
+        let Some(value) = value else {
+            if variable.required {
+                return Err(format!(
+                    "Template variable '{}' is required.",
+                    variable.name
+                ));
+            }
+            continue;
+        };
+        context.insert(variable.name.clone(), validate_value(variable, value)?);
+    }
+
+    Ok(context)
+}
+
+pub fn render_template(source: &str, context: &Map<String, Value>) -> Result<String, String> {
+    environment()
+        .render_str(source, context)
+        .map_err(|err| format!("Unable to render prompt template: {}", err))
+}
+
+fn environment() -> Environment<'static> {
+    let mut environment = Environment::new();
+    environment.set_keep_trailing_newline(true);
+    environment
+}
+
+fn validate_value(variable: &TemplateVariable, value: &Value) -> Result<Value, String> {
+    let name = &variable.name;
+    match &variable.kind {
+        TemplateVariableKind::Text => match value {
+            Value::String(_) => Ok(value.clone()),
+            Value::Number(_) | Value::Bool(_) => Ok(Value::String(value.to_string())),
+            _ => Err(format!("Template variable '{}' must be text.", name)),
+        },
+        TemplateVariableKind::Enum { options, multiple } => {
+            let check = |choice: &Value| match choice.as_str() {
+                Some(text) if options.iter().any(|option| option == text) => Ok(choice.clone()),
+                _ => Err(format!(
+                    "Template variable '{}' must be one of: {}.",
+                    name,
+                    options.join(", ")
+                )),
+            };
+            match value {
+                Value::Array(items) if *multiple => items
+                    .iter()
+                    .map(check)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::Array),
+                _ if *multiple => check(value).map(|choice| Value::Array(vec![choice])),
+                _ => check(value),
+            }
+        }
+        TemplateVariableKind::Number { min, max } => {
+            let number = value
+                .as_f64()
+                .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+                .ok_or_else(|| format!("Template variable '{}' must be a number.", name))?;
+            let out_of_range = match (min, max) {
+                (Some(min), Some(max)) if number < *min || number > *max => {
+                    Some(format!("between {} and {}", min, max))
+                }
+                (Some(min), None) if number < *min => Some(format!("at least {}", min)),
+                (None, Some(max)) if number > *max => Some(format!("at most {}", max)),
+                _ => None,
+            };
+            if let Some(range) = out_of_range {
+                return Err(format!("Template variable '{}' must be {}.", name, range));
+            }
+            Ok(match value {
+                Value::Number(_) => value.clone(),
+                // Keep "3" rendering as 3 rather than 3.0.
+                _ if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => {
+                    Value::from(number as i64)
+                }
+                _ => Value::from(number),
+            })
+        }
+        TemplateVariableKind::Image => {
+            let slot = value
+                .as_str()
+                .map(|slot| slot.trim().trim_start_matches('{').trim_end_matches('}'))
+                .filter(|slot| !slot.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "Template variable '{}' must name an image slot such as img_1.",
+                        name
+                    )
+                })?;
+            Ok(Value::String(format!("{{{}}}", slot)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variable(spec: Value) -> TemplateVariable {
+        serde_json::from_value(spec).expect("variable")
+    }
+
+    fn values(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().expect("object")
+    }
+
+    #[test]
+    fn numbers_are_range_checked() {
+        let size = variable(json!({ "name": "size", "type": "number", "min": 1, "max": 10 }));
+        assert_eq!(validate_value(&size, &json!(4.5)).unwrap(), json!(4.5));
+        assert!(validate_value(&size, &json!(0)).is_err());
+        assert!(validate_value(&size, &json!(11)).is_err());
+        assert!(validate_value(&size, &json!("ten")).is_err());
+
+        let at_least = variable(json!({ "name": "count", "type": "number", "min": 2 }));
+        let err = validate_value(&at_least, &json!(1)).unwrap_err();
+        assert!(err.contains("at least 2"), "{err}");
+    }
+
+    #[test]
+    fn numeric_text_becomes_a_number() {
+        let count = variable(json!({ "name": "count", "type": "number" }));
+        assert_eq!(validate_value(&count, &json!("3")).unwrap(), json!(3));
+        assert_eq!(validate_value(&count, &json!(" 2.5 ")).unwrap(), json!(2.5));
+
+        let context = resolve_variables(&[count], &values(json!({ "count": "3" }))).unwrap();
+        assert_eq!(
+            render_template("{{ count }} cats", &context).unwrap(),
+            "3 cats"
+        );
+    }
+
+    #[test]
+    fn text_accepts_scalars() {
+        let mood = variable(json!({ "name": "mood", "type": "text" }));
+        assert_eq!(validate_value(&mood, &json!(true)).unwrap(), json!("true"));
+        assert!(validate_value(&mood, &json!(["a"])).is_err());
+    }
+
+    #[test]
+    fn enum_values_must_be_options() {
+        let style = variable(json!({ "name": "style", "type": "enum", "options": ["oil", "ink"] }));
+        assert_eq!(validate_value(&style, &json!("ink")).unwrap(), json!("ink"));
+        assert!(validate_value(&style, &json!("pastel")).is_err());
+        assert!(validate_value(&style, &json!(["ink"])).is_err());
+    }
+
+    #[test]
+    fn single_value_is_wrapped_for_multiple_enums() {
+        let styles = variable(json!({
+            "name": "styles",
+            "type": "enum",
+            "options": ["oil", "ink"],
+            "multiple": true
+        }));
+        assert_eq!(
+            validate_value(&styles, &json!("oil")).unwrap(),
+            json!(["oil"])
+        );
+        assert_eq!(
+            validate_value(&styles, &json!(["ink", "oil"])).unwrap(),
+            json!(["ink", "oil"])
+        );
+        assert!(validate_value(&styles, &json!(["ink", "pastel"])).is_err());
+    }
+
+    #[test]
+    fn image_slots_are_normalised_to_placeholders() {
+        let subject = variable(json!({ "name": "subject", "type": "image" }));
+        assert_eq!(
+            validate_value(&subject, &json!("img_1")).unwrap(),
+            json!("{img_1}")
+        );
+        assert_eq!(
+            validate_value(&subject, &json!(" {img_2} ")).unwrap(),
+            json!("{img_2}")
+        );
+        assert!(validate_value(&subject, &json!("{}")).is_err());
+        assert!(validate_value(&subject, &json!(1)).is_err());
+    }
+
+    #[test]
+    fn defaults_fill_missing_values_and_required_ones_must_be_set() {
+        let variables = [
+            variable(json!({ "name": "style", "type": "text", "default": "watercolour" })),
+            variable(json!({ "name": "subject", "type": "text", "required": true })),
+            variable(json!({ "name": "extra", "type": "text" })),
+        ];
+
+        assert!(resolve_variables(&variables, &Map::new()).is_err());
+        let context = resolve_variables(
+            &variables,
+            &values(json!({ "subject": "a fox", "extra": null })),
+        )
+        .unwrap();
+        assert_eq!(context.get("style"), Some(&json!("watercolour")));
+        assert!(!context.contains_key("extra"));
+
+        let rendered = render_template(
+            "{{ subject }} in {{ style }}{% if extra %}, {{ extra }}{% endif %}",
+            &context,
+        )
+        .unwrap();
+        assert_eq!(rendered, "a fox in watercolour");
+    }
+
+    #[test]
+    fn unknown_values_are_rejected() {
+        let variables = [variable(json!({ "name": "style", "type": "text" }))];
+        let err = resolve_variables(&variables, &values(json!({ "colour": "red" }))).unwrap_err();
+        assert!(err.contains("colour"), "{err}");
+    }
+
+    #[test]
+    fn captions_name_is_reserved() {
+        let captions = variable(json!({ "name": CAPTIONS_VARIABLE, "type": "text" }));
+        assert!(validate_template(&["{{ captions }}"], &[captions]).is_err());
+    }
+
+    #[test]
+    fn declarations_and_syntax_are_validated() {
+        let text = |name: &str| variable(json!({ "name": name, "type": "text" }));
+        assert!(validate_template(&["{{ a }}"], &[text("a")]).is_ok());
+        assert!(validate_template(&[""], &[text("1st")]).is_err());
+        assert!(validate_template(&[""], &[text("a"), text("a")]).is_err());
+        assert!(validate_template(&["{% if %}"], &[]).is_err());
+
+        let empty_enum = variable(json!({ "name": "style", "type": "enum", "options": [] }));
+        assert!(validate_template(&[""], &[empty_enum]).is_err());
+        let bad_default =
+            variable(json!({ "name": "size", "type": "number", "max": 3, "default": 5 }));
+        assert!(validate_template(&[""], &[bad_default]).is_err());
+    }
+
+    #[test]
+    fn legacy_placeholders_pass_through() {
+        let rendered = render_template("A portrait of {img_1}", &Map::new()).unwrap();
+        assert_eq!(rendered, "A portrait of {img_1}");
+    }
+}